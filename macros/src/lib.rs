@@ -0,0 +1,206 @@
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+#[macro_use]
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{GenericParam, ItemTrait, Path, TraitItem, Type, TypeParamBound, WherePredicate};
+
+struct Options {
+    sync: bool,
+    clone: bool,
+    eq: bool,
+    hash: bool,
+    ord: Option<Path>,
+    debug: bool,
+    error: bool,
+    krate: Path,
+}
+
+/// Adds downcasting support to the trait it is placed on: injects the 'Downcast' supertrait and
+/// invokes 'impl_downcast!' with the generics, bounds and associated types read from the trait.
+///
+/// '#[downcast(sync)]' injects 'DowncastSync' instead, adding 'Arc' downcasting,
+/// '#[downcast(clone)]' also injects 'DowncastClone', making 'Box<dyn Trait>' cloneable,
+/// '#[downcast(eq)]' injects 'cmp::DynEq' and implements 'PartialEq' for 'dyn Trait',
+/// '#[downcast(hash)]' injects 'cmp::DynHash' and also implements 'Eq' and 'Hash',
+/// '#[downcast(ord = key)]' injects 'cmp::DynOrd' and also implements 'Eq', 'PartialOrd' and 'Ord',
+/// ordering objects of different types by 'key',
+/// '#[downcast(debug)]' implements 'Debug' through the formatters registered in 'debug',
+/// '#[downcast(error)]' implements 'error::ErrorChain' for traits extending 'Error', and
+/// '#[downcast(crate = path)]' names the downcast crate when it is not reachable as '::wzDowncast'.
+#[proc_macro_attribute]
+pub fn downcast(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut options = Options {
+        sync: false,
+        clone: false,
+        eq: false,
+        hash: false,
+        ord: None,
+        debug: false,
+        error: false,
+        krate: parse_quote!(::wzDowncast),
+    };
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("sync") {
+            options.sync = true;
+            Ok(())
+        } else if meta.path.is_ident("clone") {
+            options.clone = true;
+            Ok(())
+        } else if meta.path.is_ident("eq") {
+            options.eq = true;
+            Ok(())
+        } else if meta.path.is_ident("hash") {
+            options.hash = true;
+            Ok(())
+        } else if meta.path.is_ident("ord") {
+            if !meta.input.peek(Token![=]) {
+                return Err(meta.error("'ord' needs a key ordering the concrete types, as in 'ord = key'"));
+            }
+            options.ord = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("debug") {
+            options.debug = true;
+            Ok(())
+        } else if meta.path.is_ident("error") {
+            options.error = true;
+            Ok(())
+        } else if meta.path.is_ident("crate") {
+            options.krate = meta.value()?.parse()?;
+            Ok(())
+        } else {
+            Err(meta.error("unsupported downcast option"))
+        }
+    });
+    parse_macro_input!(args with parser);
+    let item = parse_macro_input!(input as ItemTrait);
+    expand(&options, item)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(options: &Options, mut item: ItemTrait) -> syn::Result<TokenStream2> {
+    let krate = &options.krate;
+    let (supertrait, wanted): (Path, &[&str]) = if options.sync {
+        (parse_quote!(#krate::DowncastSync), &["DowncastSync"])
+    } else {
+        (parse_quote!(#krate::Downcast), &["Downcast", "DowncastSync"])
+    };
+    if !item.supertraits.iter().any(|bound| is_one_of(bound, wanted)) {
+        item.colon_token.get_or_insert_with(Default::default);
+        item.supertraits.push(parse_quote!(#supertrait));
+    }
+    if options.clone && !item.supertraits.iter().any(|bound| is_one_of(bound, &["DowncastClone"])) {
+        item.supertraits.push(parse_quote!(#krate::DowncastClone));
+    }
+    if options.hash && !item.supertraits.iter().any(|bound| is_one_of(bound, &["DynHash"])) {
+        item.supertraits.push(parse_quote!(#krate::cmp::DynHash));
+    }
+    if options.ord.is_some() && !item.supertraits.iter().any(|bound| is_one_of(bound, &["DynOrd"])) {
+        item.supertraits.push(parse_quote!(#krate::cmp::DynOrd));
+    }
+    let eq_traits = &["DynEq", "DynHash", "DynOrd"];
+    if options.eq && !item.supertraits.iter().any(|bound| is_one_of(bound, eq_traits)) {
+        item.supertraits.push(parse_quote!(#krate::cmp::DynEq));
+    }
+
+    let mut args = Vec::new();
+    let mut preds = Vec::new();
+    for param in &item.generics.params {
+        match *param {
+            GenericParam::Lifetime(ref def) => {
+                let lifetime = &def.lifetime;
+                args.push(quote!(#lifetime));
+                if !def.bounds.is_empty() {
+                    let bounds = &def.bounds;
+                    preds.push(quote!(#lifetime: #bounds));
+                }
+            }
+            GenericParam::Type(ref def) => {
+                let ident = &def.ident;
+                args.push(quote!(#ident));
+                if !def.bounds.is_empty() {
+                    let bounds = &def.bounds;
+                    preds.push(quote!(#ident: #bounds));
+                }
+            }
+            GenericParam::Const(ref def) => {
+                let ident = &def.ident;
+                let ty = &def.ty;
+                args.push(quote!(const #ident: #ty));
+            }
+        }
+    }
+    for trait_item in &item.items {
+        if let TraitItem::Type(ref assoc) = *trait_item {
+            if !assoc.generics.params.is_empty() || assoc.generics.where_clause.is_some() {
+                return Err(syn::Error::new_spanned(
+                    assoc,
+                    "generic associated types cannot be used in trait objects",
+                ));
+            }
+            let ident = &assoc.ident;
+            args.push(quote!(#ident = #ident));
+            if !assoc.bounds.is_empty() {
+                let bounds = &assoc.bounds;
+                preds.push(quote!(#ident: #bounds));
+            }
+        }
+    }
+    if let Some(ref where_clause) = item.generics.where_clause {
+        // Predicates on 'Self' are supertraits in disguise and hold for the trait object anyway.
+        preds.extend(
+            where_clause
+                .predicates
+                .iter()
+                .filter(|pred| !is_self_predicate(pred))
+                .map(|pred| quote!(#pred)),
+        );
+    }
+
+    let ident = &item.ident;
+    let sync = if options.sync { quote!(sync) } else { quote!() };
+    let clone = if options.clone { quote!(clone) } else { quote!() };
+    let eq = if options.eq { quote!(eq) } else { quote!() };
+    let hash = if options.hash { quote!(hash) } else { quote!() };
+    let ord = match options.ord {
+        Some(ref key) => quote!(ord(#key)),
+        None => quote!(),
+    };
+    let debug = if options.debug { quote!(debug) } else { quote!() };
+    let error = if options.error { quote!(error) } else { quote!() };
+    let where_clause = if preds.is_empty() {
+        quote!()
+    } else {
+        quote!(where #(#preds),*)
+    };
+    Ok(quote! {
+        #item
+        #krate::impl_downcast!(#clone #eq #hash #ord #debug #error #sync #ident<#(#args),*> #where_clause);
+    })
+}
+
+fn is_one_of(bound: &TypeParamBound, names: &[&str]) -> bool {
+    match *bound {
+        TypeParamBound::Trait(ref bound) => bound
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| names.iter().any(|name| segment.ident == name)),
+        _ => false,
+    }
+}
+
+fn is_self_predicate(pred: &WherePredicate) -> bool {
+    match *pred {
+        WherePredicate::Type(ref pred) => match pred.bounded_ty {
+            Type::Path(ref ty) => ty.qself.is_none() && ty.path.is_ident("Self"),
+            _ => false,
+        },
+        _ => false,
+    }
+}
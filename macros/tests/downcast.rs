@@ -0,0 +1,169 @@
+extern crate wzDowncast;
+extern crate wzDowncast as renamed;
+extern crate wz_downcast_macros;
+
+use std::fmt::Display;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use wz_downcast_macros::downcast;
+
+macro_rules! test_mod {
+    (
+        $test_name:ident,
+        trait $base_trait:path {$($base_impl:tt)*},
+        type $base_type:ty,
+        {$($def:tt)*}
+        $(sync {$($sync_test:tt)*})*
+        $(extra {$($extra_test:tt)*})*
+    ) => {
+        mod $test_name {
+            use super::*;
+
+            // A trait that can be downcast.
+            $($def)*
+
+            // Concrete types implementing Base.
+            #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            struct Foo(u32);
+            impl $base_trait for Foo {$($base_impl)*}
+            #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            struct Bar;
+            impl $base_trait for Bar {$($base_impl)*}
+
+            #[test]
+            fn test() {
+                let mut base: Box<$base_type> = Box::new(Foo(42));
+                assert!(base.is::<Foo>());
+                assert!(!base.is::<Bar>());
+                assert_eq!(base.downcast_ref::<Foo>().map(|foo| foo.0), Some(42));
+                assert!(base.downcast_ref::<Bar>().is_none());
+
+                base.downcast_mut::<Foo>().unwrap().0 = 6*9;
+                assert!(base.downcast_mut::<Bar>().is_none());
+
+                let base = base.downcast::<Bar>().err().unwrap();
+                assert_eq!(base.downcast::<Foo>().ok().unwrap().0, 6*9);
+
+                let base: Rc<$base_type> = Rc::new(Foo(7));
+                let base = base.downcast_rc::<Bar>().err().unwrap();
+                assert_eq!(base.downcast_rc::<Foo>().ok().unwrap().0, 7);
+
+                $({$($sync_test)*})*
+
+                $({$($extra_test)*})*
+            }
+        }
+    };
+}
+
+macro_rules! sync_test {
+    ($base_type:ty) => {{
+        let base: Arc<$base_type> = Arc::new(Foo(7));
+        let base = base.downcast_arc::<Bar>().err().unwrap();
+        assert_eq!(base.downcast_arc::<Foo>().ok().unwrap().0, 7);
+    }};
+}
+
+test_mod!(non_generic, trait Base {}, type dyn Base, {
+    #[downcast]
+    trait Base {}
+});
+
+test_mod!(existing_supertrait, trait Base {}, type dyn Base, {
+    #[downcast]
+    trait Base: wzDowncast::Downcast {}
+});
+
+test_mod!(constrained_generic, trait Base<u32> {}, type dyn Base<u32>, {
+    #[downcast]
+    pub trait Base<T: Copy>: Send where T: Default {}
+});
+
+test_mod!(associated, trait Base<u32> {type H = u8;}, type dyn Base<u32, H = u8>, {
+    #[downcast]
+    trait Base<T> {
+        type H: Display;
+    }
+});
+
+test_mod!(mixed_generic, trait Base<'static, u32, 4> {}, type dyn Base<'static, u32, 4>, {
+    #[downcast]
+    trait Base<'a, T, const N: usize> where Self: 'a {}
+});
+
+test_mod!(sync_generic, trait Base<u32> {}, type dyn Base<u32>, {
+    #[downcast(sync)]
+    trait Base<T> {}
+} sync {
+    sync_test!(dyn Base<u32>)
+});
+
+test_mod!(clone, trait Base<u32> {}, type dyn Base<u32>, {
+    #[downcast(clone, sync)]
+    trait Base<T> {}
+} sync {
+    sync_test!(dyn Base<u32>)
+} extra {
+    let base: Box<dyn Base<u32>> = Box::new(Foo(3));
+    assert_eq!(base.clone().downcast_ref::<Foo>().map(|foo| foo.0), Some(3));
+});
+
+test_mod!(eq_hash, trait Base<u32> {}, type dyn Base<u32>, {
+    #[downcast(eq, hash)]
+    trait Base<T> {}
+} extra {
+    let base: Box<dyn Base<u32>> = Box::new(Foo(3));
+    assert!(base == Box::new(Foo(3)) as Box<dyn Base<u32>>);
+    assert!(base != Box::new(Bar) as Box<dyn Base<u32>>);
+});
+
+test_mod!(ord, trait Base<u32> {}, type dyn Base<u32>, {
+    fn key<T: 'static>(base: &dyn Base<T>) -> bool { base.concrete_type_id() == ::std::any::TypeId::of::<Foo>() }
+    #[downcast(ord = key)]
+    trait Base<T> {}
+} extra {
+    let foo: Box<dyn Base<u32>> = Box::new(Foo(3));
+    assert!(foo > Box::new(Bar) as Box<dyn Base<u32>>);
+    assert!(foo < Box::new(Foo(4)) as Box<dyn Base<u32>>);
+});
+
+test_mod!(debug, trait Base {}, type dyn Base, {
+    #[downcast(debug, sync)]
+    trait Base {}
+} sync {
+    sync_test!(dyn Base)
+} extra {
+    let base: Box<dyn Base> = Box::new(Foo(3));
+    assert!(format!("{:?}", base).ends_with("::Foo"));
+});
+
+test_mod!(error, trait Base {}, type dyn Base, {
+    #[downcast(error)]
+    trait Base: ::std::error::Error {}
+    impl ::std::fmt::Display for Foo {
+        fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result { f.write_str("foo") }
+    }
+    impl ::std::error::Error for Foo {}
+    impl ::std::fmt::Display for Bar {
+        fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result { f.write_str("bar") }
+    }
+    impl ::std::error::Error for Bar {}
+} extra {
+    use wzDowncast::error::ErrorChain;
+    let base: Box<dyn Base> = Box::new(Foo(1));
+    assert_eq!(base.find_cause::<Foo>().map(|foo| foo.0), Some(1));
+    assert!(base.root_cause_as::<Bar>().is_none());
+});
+
+test_mod!(renamed_crate, trait Base {}, type dyn Base, {
+    #[downcast(sync, crate = ::renamed)]
+    trait Base {}
+} sync {
+    sync_test!(dyn Base)
+});
+
+test_mod!(reexported, trait Base {}, type dyn Base, {
+    #[wzDowncast::downcast]
+    trait Base {}
+});
@@ -0,0 +1,117 @@
+//! Cross-casting between trait objects. Concrete types register the traits they implement with
+//! 'register_cast!', after which the 'cast_ref', 'cast_mut' and 'cast_box' methods generated by
+//! 'impl_downcast!' turn a '&dyn Component' into a '&dyn Renderable' whenever the object behind it
+//! was registered for 'dyn Renderable'.
+
+use std::any::{Any, TypeId};
+
+use super::Downcast;
+use super::registry::{self, Registry};
+
+type Key = (TypeId, TypeId);
+
+static REGISTRY: Registry<Key, Box<dyn Any + Send + Sync>> = Registry::new();
+
+/// Conversions from one concrete type, erased behind 'Any', to the trait object 'T'. Built by
+/// 'register_cast!', where the unsizing coercion to 'T' can be spelled out.
+#[doc(hidden)]
+pub struct Caster<T: ?Sized + 'static> {
+    cast_ref: fn(&dyn Any) -> Option<&T>,
+    cast_mut: fn(&mut dyn Any) -> Option<&mut T>,
+    cast_box: fn(Box<dyn Any>) -> Box<T>,
+}
+
+impl<T: ?Sized + 'static> Clone for Caster<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized + 'static> Copy for Caster<T> {}
+
+impl<T: ?Sized + 'static> Caster<T> {
+    #[doc(hidden)]
+    pub fn new(
+        cast_ref: fn(&dyn Any) -> Option<&T>,
+        cast_mut: fn(&mut dyn Any) -> Option<&mut T>,
+        cast_box: fn(Box<dyn Any>) -> Box<T>,
+    ) -> Self {
+        Caster { cast_ref, cast_mut, cast_box }
+    }
+}
+
+/// Records that 'S' can be viewed as 'T'. Called by 'register_cast!'.
+#[doc(hidden)]
+pub fn register<S: Any, T: ?Sized + 'static>(caster: Caster<T>) {
+    REGISTRY.insert((TypeId::of::<S>(), TypeId::of::<T>()), Box::new(caster));
+}
+
+/// Unboxes a value of the concrete type a cast was registered for. Called by 'register_cast!'.
+#[doc(hidden)]
+pub fn unbox<S: Any>(any: Box<dyn Any>) -> Box<S> {
+    registry::expect_box(any)
+}
+
+fn caster<T: ?Sized + 'static>(concrete: TypeId) -> Option<Caster<T>> {
+    REGISTRY.find(&(concrete, TypeId::of::<T>()), |caster| {
+        caster.downcast_ref::<Caster<T>>().copied()
+    })
+}
+
+/// Returns whether objects of the concrete type 'concrete' have been registered as 'T'.
+pub fn can_cast<T: ?Sized + 'static>(concrete: TypeId) -> bool {
+    caster::<T>(concrete).is_some()
+}
+
+/// Views the object behind 'any' as 'T', if its concrete type was registered for 'T'.
+pub fn cast_ref<T: ?Sized + 'static>(any: &dyn Any) -> Option<&T> {
+    caster::<T>(any.type_id()).and_then(|caster| (caster.cast_ref)(any))
+}
+
+/// Mutable counterpart to 'cast_ref'.
+pub fn cast_mut<T: ?Sized + 'static>(any: &mut dyn Any) -> Option<&mut T> {
+    caster::<T>((*any).type_id()).and_then(move |caster| (caster.cast_mut)(any))
+}
+
+/// Converts the boxed object to 'Box<T>', handing the box back unchanged if its concrete type was
+/// not registered for 'T'.
+pub fn cast_box<T: ?Sized + 'static, S: ?Sized + Downcast>(boxed: Box<S>) -> Result<Box<T>, Box<S>> {
+    match caster::<T>((*boxed).concrete_type_id()) {
+        Some(caster) => Ok((caster.cast_box)(boxed.into_any())),
+        None => Err(boxed),
+    }
+}
+
+/// Registers a concrete type as implementing one or more traits, enabling cross-casts to them:
+///
+/// ```
+/// #[macro_use]
+/// extern crate wzDowncast;
+/// use wzDowncast::prelude::*;
+///
+/// trait Component: Downcast {}
+/// impl_downcast!(Component);
+/// trait Renderable { fn render(&self) -> String; }
+///
+/// struct Sprite;
+/// impl Component for Sprite {}
+/// impl Renderable for Sprite { fn render(&self) -> String { "sprite".into() } }
+///
+/// fn main() {
+///     register_cast!(Sprite => dyn Renderable);
+///     let component: Box<dyn Component> = Box::new(Sprite);
+///     assert_eq!(component.cast_ref::<dyn Renderable>().unwrap().render(), "sprite");
+/// }
+/// ```
+#[macro_export]
+macro_rules! register_cast {
+    ($concrete:ty => $($target:ty),+ $(,)*) => {
+        $(
+            $crate::cast::register::<$concrete, $target>($crate::cast::Caster::new(
+                |any| any.downcast_ref::<$concrete>().map(|value| value as &$target),
+                |any| any.downcast_mut::<$concrete>().map(|value| value as &mut $target),
+                |any| $crate::cast::unbox::<$concrete>(any) as $crate::__private::Box<$target>,
+            ));
+        )+
+    };
+}
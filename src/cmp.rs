@@ -0,0 +1,66 @@
+//! Comparisons between trait objects whose concrete types may differ. Traits extending 'DynEq',
+//! 'DynHash' or 'DynOrd' can be declared with the 'eq', 'hash' or 'ord' option of 'impl_downcast!',
+//! which implements the standard comparison traits for 'dyn Trait' and so for 'Box<dyn Trait>'.
+
+use core::any::{Any, TypeId};
+use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
+
+use super::Downcast;
+
+/// Object-safe 'PartialEq'. Objects of different concrete types are never equal.
+pub trait DynEq: Downcast {
+    fn dyn_eq(&self, other: &dyn Any) -> bool;
+}
+
+impl<T: Any + PartialEq> DynEq for T {
+    fn dyn_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<T>().is_some_and(|other| self == other)
+    }
+}
+
+/// Object-safe 'Hash', consistent with 'DynEq': the concrete type's 'TypeId' is hashed ahead of the
+/// value, so equal values of different types do not collide.
+pub trait DynHash: DynEq {
+    fn dyn_hash(&self, state: &mut dyn Hasher);
+}
+
+impl<T: Any + Eq + Hash> DynHash for T {
+    fn dyn_hash(&self, mut state: &mut dyn Hasher) {
+        TypeId::of::<T>().hash(&mut state);
+        self.hash(&mut state);
+    }
+}
+
+/// Object-safe 'Ord' within a concrete type. Ordering objects of different types is left to
+/// 'cmp_by_key'.
+pub trait DynOrd: DynEq {
+    /// Compares with 'other' if it has the same concrete type.
+    fn dyn_cmp(&self, other: &dyn Any) -> Option<Ordering>;
+}
+
+impl<T: Any + Ord> DynOrd for T {
+    fn dyn_cmp(&self, other: &dyn Any) -> Option<Ordering> {
+        other.downcast_ref::<T>().map(|other| self.cmp(other))
+    }
+}
+
+/// Totally orders trait objects: objects of the same concrete type compare with 'Ord', others by
+/// 'key'. The order between types is thus as stable as the key, so give each type a distinct key.
+/// Types with equal keys are ordered by type name and then by 'TypeId', neither of which is stable:
+/// type names may change between compiler versions and need not be unique, and the order of
+/// 'TypeId's may change between builds.
+pub fn cmp_by_key<B, K, F>(a: &B, b: &B, key: F) -> Ordering
+where
+    B: ?Sized + DynOrd,
+    K: Ord,
+    F: Fn(&B) -> K,
+{
+    match DynOrd::dyn_cmp(a, Downcast::as_any(b)) {
+        Some(ordering) => ordering,
+        None => key(a)
+            .cmp(&key(b))
+            .then_with(|| Downcast::concrete_type_name(a).cmp(Downcast::concrete_type_name(b)))
+            .then_with(|| Downcast::concrete_type_id(a).cmp(&Downcast::concrete_type_id(b))),
+    }
+}
@@ -0,0 +1,52 @@
+//! 'Debug' for trait objects whose trait does not extend 'Debug'. Concrete types register their
+//! 'Debug' implementations with 'register', after which trait objects declared with the 'debug'
+//! option of 'impl_downcast!' format through them. Objects of unregistered types, and all objects
+//! without the 'std' feature, print their concrete type name instead.
+
+use core::any::{Any, TypeId};
+use core::fmt;
+use super::Downcast;
+#[cfg(feature = "std")]
+use super::registry::{self, Registry};
+
+type DebugFn = fn(&dyn Any, &mut fmt::Formatter<'_>) -> fmt::Result;
+
+#[cfg(feature = "std")]
+static REGISTRY: Registry<TypeId, DebugFn> = Registry::new();
+
+#[cfg(feature = "std")]
+fn fmt_as<T: Any + fmt::Debug>(value: &dyn Any, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt::Debug::fmt(registry::expect_ref::<T>(value), f)
+}
+
+/// Registers the 'Debug' implementation of 'T' for formatting trait objects.
+#[cfg(feature = "std")]
+pub fn register<T: Any + fmt::Debug>() {
+    REGISTRY.insert(TypeId::of::<T>(), fmt_as::<T> as DebugFn);
+}
+
+/// Returns whether objects of the concrete type 'concrete' have a registered 'Debug'
+/// implementation.
+#[cfg(feature = "std")]
+pub fn is_registered(concrete: TypeId) -> bool {
+    formatter(concrete).is_some()
+}
+
+#[cfg(feature = "std")]
+fn formatter(concrete: TypeId) -> Option<DebugFn> {
+    REGISTRY.get(&concrete)
+}
+
+#[cfg(not(feature = "std"))]
+fn formatter(_: TypeId) -> Option<DebugFn> {
+    None
+}
+
+/// Formats the object with the registered 'Debug' implementation of its concrete type, or as the
+/// name of that type. Pass the trait object itself: a 'Box<dyn Trait>' would format as the box.
+pub fn fmt<B: ?Sized + Downcast>(object: &B, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match formatter(object.concrete_type_id()) {
+        Some(formatter) => formatter(object.as_any(), f),
+        None => f.write_str(object.concrete_type_name()),
+    }
+}
@@ -0,0 +1,139 @@
+//! Searching 'Error::source' chains by type. 'ErrorChain' is implemented for 'dyn Error' and every
+//! sized error type, and by the 'error' option of 'impl_downcast!' for trait objects of error
+//! traits such as 'trait AppError: Downcast + Error', which lose the downcasting methods of
+//! 'dyn Error'.
+//!
+//! The first link of a chain is the object the search starts from, or the error inside it for a
+//! 'Box' or 'Arc', which implement 'Error' but skip that error in 'source'. Further links are
+//! reached through 'Error::source', which only lends them immutably, so 'head_as_mut' cannot
+//! search them.
+
+use std::any::Any;
+use std::error::Error;
+use std::sync::Arc;
+
+use super::Downcast;
+
+/// Searches the chain of an error and its sources for links of a concrete type.
+pub trait ErrorChain {
+    /// Returns the first link of the chain if it has type 'T'. Implementation detail of the
+    /// provided methods.
+    #[doc(hidden)]
+    fn __head_ref<T: Error + 'static>(&self) -> Option<&T>;
+    #[doc(hidden)]
+    fn __head_mut<T: Error + 'static>(&mut self) -> Option<&mut T>;
+    #[doc(hidden)]
+    fn __head_source(&self) -> Option<&(dyn Error + 'static)>;
+
+    /// Returns the first link of type 'T', starting with the error itself.
+    fn find_cause<T: Error + 'static>(&self) -> Option<&T> {
+        self.causes_of::<T>().next()
+    }
+
+    /// Returns the first link of the chain if it has type 'T'. Sources are only lent immutably by
+    /// 'Error::source', so unlike 'find_cause' this does not search them. The error inside an 'Arc'
+    /// is only returned while the 'Arc' is not shared.
+    fn head_as_mut<T: Error + 'static>(&mut self) -> Option<&mut T> {
+        self.__head_mut::<T>()
+    }
+
+    /// Iterates over the links of type 'T', starting with the error itself.
+    fn causes_of<T: Error + 'static>(&self) -> CausesOf<'_, T> {
+        CausesOf {
+            head: self.__head_ref::<T>(),
+            next: self.__head_source(),
+        }
+    }
+
+    /// Returns the last link of the chain if it has type 'T'.
+    fn root_cause_as<T: Error + 'static>(&self) -> Option<&T> {
+        match self.__head_source() {
+            Some(mut root) => {
+                while let Some(source) = root.source() {
+                    root = source;
+                }
+                root.downcast_ref::<T>()
+            }
+            None => self.__head_ref::<T>(),
+        }
+    }
+}
+
+/// Iterator returned by 'ErrorChain::causes_of'.
+pub struct CausesOf<'a, T: 'a> {
+    head: Option<&'a T>,
+    next: Option<&'a (dyn Error + 'static)>,
+}
+
+impl<'a, T: Error + 'static> Iterator for CausesOf<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if let Some(head) = self.head.take() {
+            return Some(head);
+        }
+        while let Some(link) = self.next {
+            self.next = link.source();
+            if let Some(value) = link.downcast_ref::<T>() {
+                return Some(value);
+            }
+        }
+        None
+    }
+}
+
+/// Returns the first link of a chain if it has type 'T'. Called by the 'ErrorChain'
+/// implementations that 'impl_downcast!' generates.
+#[doc(hidden)]
+pub fn head_ref<T: Any, B: ?Sized + Downcast>(head: &B) -> Option<&T> {
+    head.as_any().downcast_ref::<T>()
+}
+
+#[doc(hidden)]
+pub fn head_mut<T: Any, B: ?Sized + Downcast>(head: &mut B) -> Option<&mut T> {
+    head.as_any_mut().downcast_mut::<T>()
+}
+
+impl<E: Error + 'static> ErrorChain for E {
+    fn __head_ref<T: Error + 'static>(&self) -> Option<&T> {
+        let head: &dyn Any = self;
+        head.downcast_ref::<T>()
+            .or_else(|| head.downcast_ref::<Box<T>>().map(|boxed| &**boxed))
+            .or_else(|| head.downcast_ref::<Arc<T>>().map(|shared| &**shared))
+    }
+    fn __head_mut<T: Error + 'static>(&mut self) -> Option<&mut T> {
+        let head: &mut dyn Any = self;
+        if head.is::<Box<T>>() {
+            return head.downcast_mut::<Box<T>>().map(|boxed| &mut **boxed);
+        }
+        if head.is::<Arc<T>>() {
+            return head.downcast_mut::<Arc<T>>().and_then(Arc::get_mut);
+        }
+        head.downcast_mut::<T>()
+    }
+    fn __head_source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source()
+    }
+}
+
+macro_rules! impl_error_chain_dyn {
+    ($($object:ty),+) => {$(
+        impl ErrorChain for $object {
+            fn __head_ref<T: Error + 'static>(&self) -> Option<&T> {
+                self.downcast_ref::<T>()
+            }
+            fn __head_mut<T: Error + 'static>(&mut self) -> Option<&mut T> {
+                self.downcast_mut::<T>()
+            }
+            fn __head_source(&self) -> Option<&(dyn Error + 'static)> {
+                self.source()
+            }
+        }
+    )+};
+}
+
+impl_error_chain_dyn!(
+    dyn Error + 'static,
+    dyn Error + Send + 'static,
+    dyn Error + Send + Sync + 'static
+);
@@ -0,0 +1,87 @@
+//! Iterator adaptors selecting the trait objects of one concrete type. 'impl_downcast!' implements
+//! the item traits below for references to its trait objects, for references to boxes of them and,
+//! for 'partition_downcast', for the boxes themselves.
+
+use core::any::{Any, TypeId};
+use core::iter::FilterMap;
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// Iterator returned by 'DowncastIter::downcast_filter'.
+pub type DowncastFilter<'a, I, T> = FilterMap<I, fn(<I as Iterator>::Item) -> Option<&'a T>>;
+
+/// Iterator returned by 'DowncastIter::downcast_filter_mut'.
+pub type DowncastFilterMut<'a, I, T> = FilterMap<I, fn(<I as Iterator>::Item) -> Option<&'a mut T>>;
+
+/// Items whose concrete type can be read.
+pub trait DowncastItem {
+    fn item_type_id(&self) -> TypeId;
+}
+
+/// Items viewable as a shared reference to their concrete type.
+pub trait DowncastRefItem<'a>: DowncastItem {
+    fn into_any_ref(self) -> &'a dyn Any;
+}
+
+/// Items viewable as a mutable reference to their concrete type.
+pub trait DowncastMutItem<'a>: DowncastItem {
+    fn into_any_mut(self) -> &'a mut dyn Any;
+}
+
+/// Owned boxes of trait objects.
+#[cfg(feature = "alloc")]
+pub trait DowncastBoxItem: DowncastItem {
+    fn into_any_box(self) -> Box<dyn Any>;
+}
+
+/// Adaptors for iterators over trait objects, implemented for every iterator.
+pub trait DowncastIter: Iterator + Sized {
+    /// Yields the items of concrete type 'T', skipping the others.
+    fn downcast_filter<'a, T: Any>(self) -> DowncastFilter<'a, Self, T>
+    where
+        Self::Item: DowncastRefItem<'a>,
+    {
+        self.filter_map(|item| item.into_any_ref().downcast_ref::<T>())
+    }
+
+    /// Mutable counterpart to 'downcast_filter'.
+    fn downcast_filter_mut<'a, T: Any>(self) -> DowncastFilterMut<'a, Self, T>
+    where
+        Self::Item: DowncastMutItem<'a>,
+    {
+        self.filter_map(|item| item.into_any_mut().downcast_mut::<T>())
+    }
+
+    /// Counts the items of concrete type 'T'.
+    fn count_of<T: Any>(self) -> usize
+    where
+        Self::Item: DowncastItem,
+    {
+        self.filter(|item| item.item_type_id() == TypeId::of::<T>()).count()
+    }
+
+    /// Splits boxed trait objects into the boxes of concrete type 'T' and the remaining objects.
+    #[cfg(feature = "alloc")]
+    fn partition_downcast<T: Any>(self) -> (Vec<Box<T>>, Vec<Self::Item>)
+    where
+        Self::Item: DowncastBoxItem,
+    {
+        let mut matched = Vec::new();
+        let mut rest = Vec::new();
+        for item in self {
+            if item.item_type_id() == TypeId::of::<T>() {
+                match item.into_any_box().downcast::<T>() {
+                    Ok(value) => matched.push(value),
+                    Err(_) => unreachable!("item_type_id disagrees with the boxed value"),
+                }
+            } else {
+                rest.push(item);
+            }
+        }
+        (matched, rest)
+    }
+}
+
+impl<I: Iterator> DowncastIter for I {}
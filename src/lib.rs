@@ -1,9 +1,66 @@
-use std::any::Any;
+// The package name is not snake case, and the lint reports crate names only at the crate root.
+#![allow(non_snake_case)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
-/// Supports conversion to 'Any'. Traits to be extended by 'downcast_impl!' must extend 'Downcast'.
+#[cfg(feature = "std")]
+extern crate core;
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "macros")]
+extern crate wz_downcast_macros;
+
+use core::any::{self, Any, TypeId};
+use core::fmt;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use alloc::rc::Rc;
+#[cfg(feature = "alloc")]
+use alloc::sync::Arc;
+
+#[cfg(feature = "std")]
+#[macro_use]
+pub mod cast;
+#[cfg(feature = "alloc")]
+pub mod type_map;
+#[cfg(feature = "std")]
+pub mod sync_type_map;
+pub mod iter;
+pub mod cmp;
+pub mod debug;
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
+pub mod wrapper;
+#[cfg(feature = "std")]
+mod registry;
+
+/// Attribute alternative to 'impl_downcast!', placed on the trait definition itself.
+#[cfg(feature = "macros")]
+pub use wz_downcast_macros::downcast;
+
+/// Supports conversion to 'Any'. Traits to be extended by 'impl_downcast!' must extend 'Downcast'.
+///
+/// The 'concrete_*' methods describe the type behind a trait object. Supertrait methods resolve
+/// on 'dyn Trait' without importing 'Downcast', so 'impl_downcast!' does not repeat them.
+///
+/// Import 'Downcast' through 'prelude', which also brings 'DowncastDeref' into scope. Imported on
+/// its own, 'Downcast' lets methods called on a 'Box', 'Rc' or 'Arc' of a trait object silently
+/// describe the pointer instead of the object.
 pub trait Downcast: Any {
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn concrete_type_name(&self) -> &'static str;
+    fn concrete_type_id(&self) -> TypeId;
+    fn concrete_size(&self) -> usize;
+    fn concrete_align(&self) -> usize;
+    fn concrete_needs_drop(&self) -> bool;
+    #[cfg(feature = "alloc")]
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+    #[cfg(feature = "alloc")]
+    fn into_any_rc(self: Rc<Self>) -> Rc<dyn Any>;
 }
 
 impl<T: Any> Downcast for T {
@@ -13,64 +70,1247 @@ impl<T: Any> Downcast for T {
     fn as_any_mut(&mut self) -> &mut dyn Any {
         self
     }
+    fn concrete_type_name(&self) -> &'static str {
+        any::type_name::<T>()
+    }
+    fn concrete_type_id(&self) -> TypeId {
+        TypeId::of::<T>()
+    }
+    fn concrete_size(&self) -> usize {
+        mem::size_of::<T>()
+    }
+    fn concrete_align(&self) -> usize {
+        mem::align_of::<T>()
+    }
+    fn concrete_needs_drop(&self) -> bool {
+        mem::needs_drop::<T>()
+    }
+    #[cfg(feature = "alloc")]
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+    #[cfg(feature = "alloc")]
+    fn into_any_rc(self: Rc<Self>) -> Rc<dyn Any> {
+        self
+    }
+}
+
+/// Extends 'Downcast' for thread-safe trait objects, adding conversion to 'Arc<Any + Send + Sync>'.
+/// Traits to be extended by 'impl_downcast!(sync ...)' must extend 'DowncastSync'.
+pub trait DowncastSync: Downcast + Send + Sync {
+    #[cfg(feature = "alloc")]
+    fn into_any_arc(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
+}
+
+impl<T: Any + Send + Sync> DowncastSync for T {
+    #[cfg(feature = "alloc")]
+    fn into_any_arc(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
+        self
+    }
+}
+
+/// Extends 'Downcast' for cloneable objects. Traits extending 'DowncastClone' can be declared with
+/// 'impl_downcast!(clone ...)', which makes 'Box<dyn Trait>' implement 'Clone'.
+///
+/// 'clone_box' trusts the address returned by '__clone_box' to hold a clone of the object, so the
+/// trait is sealed: it is implemented for every 'Clone' type and cannot be implemented otherwise.
+///
+/// ```compile_fail
+/// extern crate wzDowncast;
+/// use wzDowncast::DowncastClone;
+///
+/// struct Evil;
+/// impl DowncastClone for Evil {
+///     fn __clone_box(&self) -> *mut () {
+///         Box::into_raw(Box::new(0u8)) as *mut ()
+///     }
+/// }
+/// # fn main() {}
+/// ```
+#[cfg(feature = "alloc")]
+pub trait DowncastClone: Downcast + sealed::Sealed {
+    /// Clones the object into a new box and returns the box's address. Object safe; 'clone_box'
+    /// pairs the address with the metadata of the original trait object.
+    #[doc(hidden)]
+    fn __clone_box(&self) -> *mut ();
+}
+
+#[cfg(feature = "alloc")]
+mod sealed {
+    use core::any::Any;
+
+    pub trait Sealed {}
+
+    impl<T: Any + Clone> Sealed for T {}
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Any + Clone> DowncastClone for T {
+    fn __clone_box(&self) -> *mut () {
+        Box::into_raw(Box::new(self.clone())) as *mut ()
+    }
+}
+
+/// Clones a 'DowncastClone' trait object into a new box of the same trait object type.
+#[cfg(feature = "alloc")]
+pub fn clone_box<B: ?Sized + DowncastClone>(object: &B) -> Box<B> {
+    // The clone has the same concrete type as the object, so it shares the vtable or other
+    // metadata in the object's pointer; only the address, stored first, needs replacing.
+    let mut pointer = object as *const B;
+    unsafe {
+        let address = &mut pointer as *mut *const B as *mut *mut ();
+        assert_eq!(*address as *const (), object as *const B as *const ());
+        *address = object.__clone_box();
+        Box::from_raw(pointer as *mut B)
+    }
+}
+
+/// Copy-on-write access to the object behind an 'Rc' or 'Arc' of a 'DowncastClone' trait object.
+#[cfg(feature = "alloc")]
+pub trait MakeMutDowncast {
+    /// Returns a mutable reference to the object if it has concrete type 'T'. Like
+    /// 'Rc::make_mut', clones the object into a new allocation first if it is shared.
+    fn make_mut_downcast<T: Any>(&mut self) -> Option<&mut T>;
+}
+
+#[cfg(feature = "alloc")]
+impl<B: ?Sized + DowncastClone> MakeMutDowncast for Rc<B> {
+    fn make_mut_downcast<T: Any>(&mut self) -> Option<&mut T> {
+        if Downcast::concrete_type_id(&**self) != TypeId::of::<T>() {
+            return None;
+        }
+        if Rc::get_mut(self).is_none() {
+            *self = Rc::from(clone_box(&**self));
+        }
+        Rc::get_mut(self).and_then(|object| object.as_any_mut().downcast_mut::<T>())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<B: ?Sized + DowncastClone> MakeMutDowncast for Arc<B> {
+    fn make_mut_downcast<T: Any>(&mut self) -> Option<&mut T> {
+        if Downcast::concrete_type_id(&**self) != TypeId::of::<T>() {
+            return None;
+        }
+        if Arc::get_mut(self).is_none() {
+            *self = Arc::from(clone_box(&**self));
+        }
+        Arc::get_mut(self).and_then(|object| object.as_any_mut().downcast_mut::<T>())
+    }
+}
+
+/// Looks through smart pointers to 'Downcast' trait objects. Because 'Downcast' is implemented for
+/// every 'Any' type, 'Box<dyn Trait>' is itself 'Downcast', and with 'Downcast' in scope
+/// 'boxed.as_any()' silently describes the box rather than the object inside it. 'DowncastDeref'
+/// offers the same methods on any pointer, always dereferencing first. Imported alone, it makes
+/// such calls resolve to the pointee; imported alongside 'Downcast', as 'prelude' does, the calls
+/// become ambiguous and fail to compile instead of misbehaving, for boxes:
+///
+/// ```compile_fail
+/// #[macro_use]
+/// extern crate wzDowncast;
+/// use wzDowncast::prelude::*;
+///
+/// trait Base: Downcast {}
+/// impl_downcast!(Base);
+/// struct Foo;
+/// impl Base for Foo {}
+///
+/// fn main() {
+///     let boxed: Box<dyn Base> = Box::new(Foo);
+///     boxed.as_any();
+/// }
+/// ```
+///
+/// for 'Rc's:
+///
+/// ```compile_fail
+/// #[macro_use]
+/// extern crate wzDowncast;
+/// use std::rc::Rc;
+/// use wzDowncast::prelude::*;
+///
+/// trait Base: Downcast {}
+/// impl_downcast!(Base);
+/// struct Foo;
+/// impl Base for Foo {}
+///
+/// fn main() {
+///     let rc: Rc<dyn Base> = Rc::new(Foo);
+///     rc.concrete_type_id();
+/// }
+/// ```
+///
+/// and for 'Arc's:
+///
+/// ```compile_fail
+/// #[macro_use]
+/// extern crate wzDowncast;
+/// use std::sync::Arc;
+/// use wzDowncast::prelude::*;
+///
+/// trait Base: DowncastSync {}
+/// impl_downcast!(sync Base);
+/// struct Foo;
+/// impl Base for Foo {}
+///
+/// fn main() {
+///     let arc: Arc<dyn Base> = Arc::new(Foo);
+///     arc.concrete_type_name();
+/// }
+/// ```
+///
+/// Dereference explicitly, with '(*boxed).as_any()', or call 'DowncastDeref::as_any(&boxed)'.
+pub trait DowncastDeref {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any where Self: DerefMut;
+    fn concrete_type_name(&self) -> &'static str;
+    fn concrete_type_id(&self) -> TypeId;
+    fn concrete_size(&self) -> usize;
+    fn concrete_align(&self) -> usize;
+    fn concrete_needs_drop(&self) -> bool;
+}
+
+impl<P> DowncastDeref for P where P: Deref, P::Target: Downcast {
+    fn as_any(&self) -> &dyn Any {
+        (**self).as_any()
+    }
+    fn as_any_mut(&mut self) -> &mut dyn Any where P: DerefMut {
+        (**self).as_any_mut()
+    }
+    fn concrete_type_name(&self) -> &'static str {
+        (**self).concrete_type_name()
+    }
+    fn concrete_type_id(&self) -> TypeId {
+        (**self).concrete_type_id()
+    }
+    fn concrete_size(&self) -> usize {
+        (**self).concrete_size()
+    }
+    fn concrete_align(&self) -> usize {
+        (**self).concrete_align()
+    }
+    fn concrete_needs_drop(&self) -> bool {
+        (**self).concrete_needs_drop()
+    }
+}
+
+/// The downcasting traits, including 'DowncastDeref' so that methods called on smart pointers
+/// cannot silently resolve to the pointer itself.
+pub mod prelude {
+    pub use super::{Downcast, DowncastDeref, DowncastSync};
+    pub use super::iter::DowncastIter;
+}
+
+/// Error returned by the 'try_downcast_*' methods generated by 'impl_downcast!', naming both the
+/// requested type and the concrete type behind the trait object. Owned downcasts hand the
+/// original pointer back through 'into_original'.
+pub struct DowncastError<P = ()> {
+    expected: &'static str,
+    found: &'static str,
+    original: P,
+}
+
+impl<P> DowncastError<P> {
+    pub fn new(expected: &'static str, found: &'static str, original: P) -> Self {
+        DowncastError { expected, found, original }
+    }
+    
+    /// The name of the type that was requested.
+    pub fn expected(&self) -> &'static str {
+        self.expected
+    }
+    
+    /// The name of the concrete type behind the trait object.
+    pub fn found(&self) -> &'static str {
+        self.found
+    }
+    
+    pub fn into_original(self) -> P {
+        self.original
+    }
+}
+
+impl<P> fmt::Debug for DowncastError<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DowncastError")
+            .field("expected", &self.expected)
+            .field("found", &self.found)
+            .finish()
+    }
 }
 
-/// Adds downcasting support to traits that extend 'downcast::Downcast' by defining forwarding
-/// methods to the corresponding implementations on 'std::any::Any' in the standard library.
+impl<P> fmt::Display for DowncastError<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cannot downcast `{}` to `{}`", self.found, self.expected)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<P> std::error::Error for DowncastError<P> {}
+
+#[doc(hidden)]
+pub mod __private {
+    pub use core::any::{type_name, Any, TypeId};
+    pub use core::clone::Clone;
+    pub use core::cmp::{Eq, Ord, Ordering, PartialEq, PartialOrd};
+    pub use core::fmt;
+    pub use core::hash::{Hash, Hasher};
+    pub use core::marker::{Send, Sync};
+    pub use core::option::Option;
+    pub use core::result::Result;
+    #[cfg(feature = "std")]
+    pub use std::error::Error;
+    #[cfg(feature = "alloc")]
+    pub use alloc::boxed::Box;
+    #[cfg(feature = "alloc")]
+    pub use alloc::rc::Rc;
+    #[cfg(feature = "alloc")]
+    pub use alloc::sync::Arc;
+}
+
+/// Expands to its input only when the 'alloc' feature of this crate is enabled, since '#[cfg]'
+/// attributes in 'impl_downcast!' output would test the features of the calling crate instead.
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_alloc {
+    ($($tokens:tt)*) => {$($tokens)*};
+}
+
+#[cfg(not(feature = "alloc"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_alloc {
+    ($($tokens:tt)*) => {};
+}
+
+/// Like '__if_alloc!', for the 'std' feature.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_std {
+    ($($tokens:tt)*) => {$($tokens)*};
+}
+
+#[cfg(not(feature = "std"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __if_std {
+    ($($tokens:tt)*) => {};
+}
+
+/// Adds downcasting support to traits that extend 'Downcast'. The trait objects, alone and combined
+/// with 'Send' and 'Sync', gain inherent methods forwarding to 'core::any::Any': 'is',
+/// 'downcast_ref' and 'downcast_mut' with their 'try_' and '_unchecked' variants, and with the
+/// 'alloc' feature 'downcast' and 'downcast_rc' for boxes and 'Rc's.
+///
+/// The trait is given in one of these forms:
+///
+/// - 'impl_downcast!(Base)' for a trait without generics.
+/// - 'impl_downcast!(Base<T, U>)' or 'impl_downcast!(Base<T> where T: Copy)' for a generic trait,
+///   covering every instantiation. Lifetimes, 'const' parameters and associated types bound to
+///   type parameters, as in 'Base<T, H = H>', are accepted too.
+/// - 'impl_downcast!(concrete Base<u32>)' for a single instantiation, including associated types
+///   bound to types, as in 'Base<H = f32>'.
+/// - 'impl_downcast!(sync Base<T>)' or 'impl_downcast!(sync concrete Base<u32>)' for a trait
+///   extending 'DowncastSync', adding 'downcast_arc' and 'try_downcast_arc'.
+///
+/// The generated methods are inherent and need no import. Import 'Downcast' and the other
+/// downcasting traits through 'prelude', so that their methods called on smart pointers to the
+/// trait objects cannot silently resolve to the pointer itself.
+///
+/// The trait may be named by a path. A keyword of this macro followed by '::' is read as the first
+/// segment of the path, as in 'impl_downcast!(sync::Base)' for a trait in a module named 'sync'.
+/// To place a path starting with '::' after options, 'sync' or 'concrete', end them with ';', as in
+/// 'impl_downcast!(sync; ::plugins::Base)': the rest is then always read as the trait.
+///
+/// Options, placed before the form in any combination, implement more traits for 'dyn Base':
+///
+/// - 'clone': 'Box<dyn Base>' implements 'Clone', and the trait objects gain 'clone_box'. The trait
+///   must extend 'DowncastClone'.
+/// - 'eq': 'dyn Base' implements 'PartialEq'. The trait must extend 'cmp::DynEq'.
+/// - 'hash': 'dyn Base' implements 'PartialEq', 'Eq' and 'Hash'. The trait must extend
+///   'cmp::DynHash'.
+/// - 'ord(key)': 'dyn Base' implements 'PartialEq', 'Eq', 'PartialOrd' and 'Ord', ordering objects
+///   of different types by 'key', a function of the trait object, as described in
+///   'cmp::cmp_by_key'. The trait must extend 'cmp::DynOrd'.
+/// - 'debug': 'dyn Base' implements 'Debug' through the formatters registered in 'debug'.
+/// - 'error': 'dyn Base' implements 'error::ErrorChain'. The trait must extend 'Error'.
+///
+/// ```
+/// #[macro_use]
+/// extern crate wzDowncast;
+/// use wzDowncast::cmp::{DynHash, DynOrd};
+///
+/// trait Task: DynHash + DynOrd {
+///     fn priority(&self) -> u32;
+/// }
+/// impl_downcast!(hash ord(Task::priority) Task);
+///
+/// #[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// struct Flush(u32);
+/// impl Task for Flush {
+///     fn priority(&self) -> u32 { 1 }
+/// }
+///
+/// fn main() {
+///     let task: &dyn Task = &Flush(1);
+///     assert!(task.is::<Flush>());
+///     assert!(task == &Flush(1) as &dyn Task);
+///     assert!(task < &Flush(2) as &dyn Task);
+/// }
+/// ```
 #[macro_export]
 macro_rules! impl_downcast {
     (@impl_full
-        $trait_:ident [$($param_types:tt)*]
-        for [$($forall_types:ident),*]
+        [$($options:tt)*] [$($trait_:tt)+] [$($param_types:tt)*]
+        for [$($generics:tt)*] types [$($forall_types:ident),*]
+        where [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            @impl_auto
+                [$($trait_)+] [$($param_types)*]
+                for [$($generics)*] types [$($forall_types),*]
+                where [$($preds)*]
+                [{
+                    $crate::impl_downcast! {@impl_body [$($trait_)+] [$($param_types)*]}
+                }]
+        }
+        $crate::impl_downcast! {
+            @impl_traits
+                [$($trait_)+] [$($param_types)*]
+                for [$($generics)*] types [$($forall_types),*]
+                where [$($preds)*]
+        }
+        $crate::impl_downcast! {
+            @impl_options [$($options)*]
+                [$($trait_)+] [$($param_types)*]
+                for [$($generics)*] types [$($forall_types),*]
+                where [$($preds)*]
+        }
+    };
+    
+    (@impl_full_sync
+        [$($options:tt)*] [$($trait_:tt)+] [$($param_types:tt)*]
+        for [$($generics:tt)*] types [$($forall_types:ident),*]
+        where [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            @impl_auto
+                [$($trait_)+] [$($param_types)*]
+                for [$($generics)*] types [$($forall_types),*]
+                where [$($preds)*]
+                [{
+                    $crate::impl_downcast! {@impl_body [$($trait_)+] [$($param_types)*]}
+                    $crate::impl_downcast! {@impl_body_sync [$($trait_)+] [$($param_types)*]}
+                }]
+        }
+        $crate::impl_downcast! {
+            @impl_traits
+                [$($trait_)+] [$($param_types)*]
+                for [$($generics)*] types [$($forall_types),*]
+                where [$($preds)*]
+        }
+        $crate::impl_downcast! {
+            @impl_options [$($options)*]
+                [$($trait_)+] [$($param_types)*]
+                for [$($generics)*] types [$($forall_types),*]
+                where [$($preds)*]
+        }
+    };
+    
+    // Trait objects with auto trait bounds are distinct types, so each combination gets its own
+    // inherent impl. The trait is parenthesized since 2015-edition 'dyn' followed by '::' is
+    // parsed as a path.
+    (@impl_auto
+        [$($trait_:tt)+] [$($param_types:tt)*]
+        for [$($generics:tt)*] types [$($forall_types:ident),*]
         where [$($preds:tt)*]
+        [$($body:tt)*]
     ) => {
-        impl_downcast! {
+        $crate::impl_downcast! {
             @inject_where
-                [impl<$($forall_types),*> dyn $trait_<$($param_types)*>]
+                [#[allow(dead_code, unused_parens)] impl<$($generics)*> dyn ($($trait_)+ <$($param_types)*>)]
+                types [$($forall_types),*]
+                where [$($preds)*]
+                [$($body)*]
+        }
+        $crate::impl_downcast! {
+            @inject_where
+                [#[allow(dead_code, unused_parens)] impl<$($generics)*> dyn ($($trait_)+ <$($param_types)*>)
+                    + $crate::__private::Send]
+                types [$($forall_types),*]
+                where [$($preds)*]
+                [$($body)*]
+        }
+        $crate::impl_downcast! {
+            @inject_where
+                [#[allow(dead_code, unused_parens)] impl<$($generics)*> dyn ($($trait_)+ <$($param_types)*>)
+                    + $crate::__private::Sync]
+                types [$($forall_types),*]
+                where [$($preds)*]
+                [$($body)*]
+        }
+        $crate::impl_downcast! {
+            @inject_where
+                [#[allow(dead_code, unused_parens)] impl<$($generics)*> dyn ($($trait_)+ <$($param_types)*>)
+                    + $crate::__private::Send + $crate::__private::Sync]
+                types [$($forall_types),*]
+                where [$($preds)*]
+                [$($body)*]
+        }
+    };
+    
+    // Expands the options given before the trait, one at a time.
+    (@impl_options []
+        [$($trait_:tt)+] [$($param_types:tt)*]
+        for [$($generics:tt)*] types [$($forall_types:ident),*]
+        where [$($preds:tt)*]
+    ) => {};
+    (@impl_options [clone $($options:tt)*]
+        [$($trait_:tt)+] [$($param_types:tt)*]
+        for [$($generics:tt)*] types [$($forall_types:ident),*]
+        where [$($preds:tt)*]
+    ) => {
+        $crate::__if_alloc! {
+            $crate::impl_downcast! {
+                @impl_auto
+                    [$($trait_)+] [$($param_types)*]
+                    for [$($generics)*] types [$($forall_types),*]
+                    where [$($preds)*]
+                    [{
+                        /// Clones the object into a new box.
+                        #[inline]
+                        pub fn clone_box(&self) -> $crate::__private::Box<Self> {
+                            $crate::clone_box(self)
+                        }
+                    }]
+            }
+            $crate::impl_downcast! {
+                @for_each_object [@impl_clone]
+                    [$($trait_)+] [$($param_types)*] [$($generics)*] [$($forall_types),*] [$($preds)*]
+            }
+        }
+        $crate::impl_downcast! {
+            @impl_options [$($options)*]
+                [$($trait_)+] [$($param_types)*]
+                for [$($generics)*] types [$($forall_types),*]
+                where [$($preds)*]
+        }
+    };
+    (@impl_options [partial_eq $($options:tt)*]
+        [$($trait_:tt)+] [$($param_types:tt)*]
+        for [$($generics:tt)*] types [$($forall_types:ident),*]
+        where [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            @for_each_object [@impl_partial_eq]
+                [$($trait_)+] [$($param_types)*] [$($generics)*] [$($forall_types),*] [$($preds)*]
+        }
+        $crate::impl_downcast! {
+            @impl_options [$($options)*]
+                [$($trait_)+] [$($param_types)*]
+                for [$($generics)*] types [$($forall_types),*]
+                where [$($preds)*]
+        }
+    };
+    (@impl_options [total_eq $($options:tt)*]
+        [$($trait_:tt)+] [$($param_types:tt)*]
+        for [$($generics:tt)*] types [$($forall_types:ident),*]
+        where [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            @for_each_object [@impl_partial_eq]
+                [$($trait_)+] [$($param_types)*] [$($generics)*] [$($forall_types),*] [$($preds)*]
+        }
+        $crate::impl_downcast! {
+            @for_each_object [@impl_eq]
+                [$($trait_)+] [$($param_types)*] [$($generics)*] [$($forall_types),*] [$($preds)*]
+        }
+        $crate::impl_downcast! {
+            @impl_options [$($options)*]
+                [$($trait_)+] [$($param_types)*]
+                for [$($generics)*] types [$($forall_types),*]
+                where [$($preds)*]
+        }
+    };
+    (@impl_options [hash $($options:tt)*]
+        [$($trait_:tt)+] [$($param_types:tt)*]
+        for [$($generics:tt)*] types [$($forall_types:ident),*]
+        where [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            @for_each_object [@impl_hash]
+                [$($trait_)+] [$($param_types)*] [$($generics)*] [$($forall_types),*] [$($preds)*]
+        }
+        $crate::impl_downcast! {
+            @impl_options [$($options)*]
+                [$($trait_)+] [$($param_types)*]
+                for [$($generics)*] types [$($forall_types),*]
+                where [$($preds)*]
+        }
+    };
+    (@impl_options [ord [$($key:tt)+] $($options:tt)*]
+        [$($trait_:tt)+] [$($param_types:tt)*]
+        for [$($generics:tt)*] types [$($forall_types:ident),*]
+        where [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            @for_each_object [@impl_ord [$($key)+]]
+                [$($trait_)+] [$($param_types)*] [$($generics)*] [$($forall_types),*] [$($preds)*]
+        }
+        $crate::impl_downcast! {
+            @impl_options [$($options)*]
+                [$($trait_)+] [$($param_types)*]
+                for [$($generics)*] types [$($forall_types),*]
+                where [$($preds)*]
+        }
+    };
+    (@impl_options [debug $($options:tt)*]
+        [$($trait_:tt)+] [$($param_types:tt)*]
+        for [$($generics:tt)*] types [$($forall_types:ident),*]
+        where [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            @for_each_object [@impl_debug]
+                [$($trait_)+] [$($param_types)*] [$($generics)*] [$($forall_types),*] [$($preds)*]
+        }
+        $crate::impl_downcast! {
+            @impl_options [$($options)*]
+                [$($trait_)+] [$($param_types)*]
+                for [$($generics)*] types [$($forall_types),*]
+                where [$($preds)*]
+        }
+    };
+    (@impl_options [error $($options:tt)*]
+        [$($trait_:tt)+] [$($param_types:tt)*]
+        for [$($generics:tt)*] types [$($forall_types:ident),*]
+        where [$($preds:tt)*]
+    ) => {
+        $crate::__if_std! {
+            $crate::impl_downcast! {
+                @for_each_object [@impl_error]
+                    [$($trait_)+] [$($param_types)*] [$($generics)*] [$($forall_types),*] [$($preds)*]
+            }
+        }
+        $crate::impl_downcast! {
+            @impl_options [$($options)*]
+                [$($trait_)+] [$($param_types)*]
+                for [$($generics)*] types [$($forall_types),*]
+                where [$($preds)*]
+        }
+    };
+    (@impl_clone
+        [$($trait_:tt)+] [$($param_types:tt)*] [$($auto:tt)*]
+        [$($generics:tt)*] [$($forall_types:ident),*] [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            @inject_where
+                [#[allow(unused_parens)] impl<$($generics)*> $crate::__private::Clone
+                    for $crate::__private::Box<dyn ($($trait_)+ <$($param_types)*>) $($auto)*>]
+                types [$($forall_types),*]
+                where [$($preds)*]
+                [{
+                    fn clone(&self) -> Self {
+                        $crate::clone_box(&**self)
+                    }
+                }]
+        }
+    };
+    (@impl_partial_eq
+        [$($trait_:tt)+] [$($param_types:tt)*] [$($auto:tt)*]
+        [$($generics:tt)*] [$($forall_types:ident),*] [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            @inject_where
+                [#[allow(unused_parens)] impl<$($generics)*> $crate::__private::PartialEq
+                    for dyn ($($trait_)+ <$($param_types)*>) $($auto)*]
+                types [$($forall_types),*]
+                where [$($preds)*]
+                [{
+                    fn eq(&self, other: &Self) -> bool {
+                        $crate::cmp::DynEq::dyn_eq(self, $crate::Downcast::as_any(other))
+                    }
+                }]
+        }
+    };
+    (@impl_eq
+        [$($trait_:tt)+] [$($param_types:tt)*] [$($auto:tt)*]
+        [$($generics:tt)*] [$($forall_types:ident),*] [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            @inject_where
+                [#[allow(unused_parens)] impl<$($generics)*> $crate::__private::Eq
+                    for dyn ($($trait_)+ <$($param_types)*>) $($auto)*]
+                types [$($forall_types),*]
+                where [$($preds)*]
+                [{}]
+        }
+    };
+    (@impl_hash
+        [$($trait_:tt)+] [$($param_types:tt)*] [$($auto:tt)*]
+        [$($generics:tt)*] [$($forall_types:ident),*] [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            @inject_where
+                [#[allow(unused_parens)] impl<$($generics)*> $crate::__private::Hash
+                    for dyn ($($trait_)+ <$($param_types)*>) $($auto)*]
+                types [$($forall_types),*]
+                where [$($preds)*]
+                [{
+                    fn hash<H: $crate::__private::Hasher>(&self, state: &mut H) {
+                        $crate::cmp::DynHash::dyn_hash(self, state)
+                    }
+                }]
+        }
+    };
+    (@impl_debug
+        [$($trait_:tt)+] [$($param_types:tt)*] [$($auto:tt)*]
+        [$($generics:tt)*] [$($forall_types:ident),*] [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            @inject_where
+                [#[allow(unused_parens)] impl<$($generics)*> $crate::__private::fmt::Debug
+                    for dyn ($($trait_)+ <$($param_types)*>) $($auto)*]
                 types [$($forall_types),*]
                 where [$($preds)*]
                 [{
-                    impl_downcast! {@impl_body $trait_ [$($param_types)*]}
+                    fn fmt(&self, f: &mut $crate::__private::fmt::Formatter<'_>) -> $crate::__private::fmt::Result {
+                        $crate::debug::fmt(self, f)
+                    }
+                }]
+        }
+    };
+    (@impl_error
+        [$($trait_:tt)+] [$($param_types:tt)*] [$($auto:tt)*]
+        [$($generics:tt)*] [$($forall_types:ident),*] [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            @inject_where
+                [#[allow(unused_parens)] impl<$($generics)*> $crate::error::ErrorChain
+                    for dyn ($($trait_)+ <$($param_types)*>) $($auto)*]
+                types [$($forall_types),*]
+                where [$($preds)*]
+                [{
+                    fn __head_ref<_T: $crate::__private::Error + 'static>(
+                        &self
+                    ) -> $crate::__private::Option<&_T> {
+                        $crate::error::head_ref::<_T, Self>(self)
+                    }
+                    fn __head_mut<_T: $crate::__private::Error + 'static>(
+                        &mut self
+                    ) -> $crate::__private::Option<&mut _T> {
+                        $crate::error::head_mut::<_T, Self>(self)
+                    }
+                    fn __head_source(
+                        &self
+                    ) -> $crate::__private::Option<&(dyn $crate::__private::Error + 'static)> {
+                        $crate::__private::Error::source(self)
+                    }
+                }]
+        }
+    };
+    (@impl_ord [$($key:tt)+]
+        [$($trait_:tt)+] [$($param_types:tt)*] [$($auto:tt)*]
+        [$($generics:tt)*] [$($forall_types:ident),*] [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            @inject_where
+                [#[allow(unused_parens)] impl<$($generics)*> $crate::__private::PartialOrd
+                    for dyn ($($trait_)+ <$($param_types)*>) $($auto)*]
+                types [$($forall_types),*]
+                where [$($preds)*]
+                [{
+                    fn partial_cmp(&self, other: &Self) -> $crate::__private::Option<$crate::__private::Ordering> {
+                        $crate::__private::Option::Some($crate::__private::Ord::cmp(self, other))
+                    }
+                }]
+        }
+        $crate::impl_downcast! {
+            @inject_where
+                [#[allow(unused_parens)] impl<$($generics)*> $crate::__private::Ord
+                    for dyn ($($trait_)+ <$($param_types)*>) $($auto)*]
+                types [$($forall_types),*]
+                where [$($preds)*]
+                [{
+                    fn cmp(&self, other: &Self) -> $crate::__private::Ordering {
+                        $crate::cmp::cmp_by_key(self, other, |object: &Self| $($key)+(object))
+                    }
                 }]
         }
     };
     
-    (@impl_body $trait_:ident [$($types:tt)*]) => {
+    // Implements this crate's traits for each trait object: 'type_map::Erase' for the trait's
+    // implementors, and the 'iter' item traits for references and boxes.
+    (@impl_traits
+        [$($trait_:tt)+] [$($param_types:tt)*]
+        for [$($generics:tt)*] types [$($forall_types:ident),*]
+        where [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            @for_each_object [@impl_traits_object]
+                [$($trait_)+] [$($param_types)*] [$($generics)*] [$($forall_types),*] [$($preds)*]
+        }
+    };
+    
+    // Invokes '@callback [trait] [params] [auto traits] [generics] [types] [preds]' for the trait
+    // object with each combination of auto traits.
+    (@for_each_object [$($callback:tt)+]
+        [$($trait_:tt)+] [$($param_types:tt)*]
+        [$($generics:tt)*] [$($forall_types:ident),*] [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            $($callback)+ [$($trait_)+] [$($param_types)*] []
+                [$($generics)*] [$($forall_types),*] [$($preds)*]
+        }
+        $crate::impl_downcast! {
+            $($callback)+ [$($trait_)+] [$($param_types)*] [+ $crate::__private::Send]
+                [$($generics)*] [$($forall_types),*] [$($preds)*]
+        }
+        $crate::impl_downcast! {
+            $($callback)+ [$($trait_)+] [$($param_types)*] [+ $crate::__private::Sync]
+                [$($generics)*] [$($forall_types),*] [$($preds)*]
+        }
+        $crate::impl_downcast! {
+            $($callback)+ [$($trait_)+] [$($param_types)*] [+ $crate::__private::Send + $crate::__private::Sync]
+                [$($generics)*] [$($forall_types),*] [$($preds)*]
+        }
+    };
+    (@impl_traits_object
+        [$($trait_:tt)+] [$($param_types:tt)*] [$($auto:tt)*]
+        [$($generics:tt)*] [$($forall_types:ident),*] [$($preds:tt)*]
+    ) => {
+        $crate::__if_alloc! {
+            $crate::impl_downcast! {
+                @append_param [@impl_erase_object [$($trait_)+] [$($param_types)*] [$($auto)*]]
+                    [[$($forall_types),*] [$($preds)*]]
+                    [] $($generics)*
+            }
+        }
+        $crate::impl_downcast! {
+            @impl_items [$($trait_)+] [$($param_types)*] [$($auto)*]
+                [$($generics)*] [$($forall_types),*] [$($preds)*]
+        }
+    };
+    (@impl_erase_object
+        [$($trait_:tt)+] [$($param_types:tt)*] [$($auto:tt)*]
+        [$($generics:tt)*] [$($forall_types:ident),*] [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            @inject_where
+                [#[allow(unused_parens)] impl<$($generics)*> $crate::type_map::Erase<_T>
+                    for dyn ($($trait_)+ <$($param_types)*>) $($auto)*]
+                types [$($forall_types),*]
+                where [_T: $($trait_)+ <$($param_types)*> $($auto)*, $($preds)*]
+                [{
+                    fn erase(value: _T) -> $crate::__private::Box<Self> {
+                        $crate::__private::Box::new(value)
+                    }
+                    fn restore_ref(erased: &Self) -> $crate::__private::Option<&_T> {
+                        erased.downcast_ref::<_T>()
+                    }
+                    fn restore_mut(erased: &mut Self) -> $crate::__private::Option<&mut _T> {
+                        erased.downcast_mut::<_T>()
+                    }
+                    fn restore(
+                        erased: $crate::__private::Box<Self>
+                    ) -> $crate::__private::Result<$crate::__private::Box<_T>, $crate::__private::Box<Self>> {
+                        erased.downcast::<_T>()
+                    }
+                }]
+        }
+    };
+    
+    (@impl_items
+        [$($trait_:tt)+] [$($param_types:tt)*] [$($auto:tt)*]
+        [$($generics:tt)*] [$($forall_types:ident),*] [$($preds:tt)*]
+    ) => {
+        $crate::impl_downcast! {
+            @inject_where
+                [#[allow(unused_parens)] impl<'__a, $($generics)*> $crate::iter::DowncastItem
+                    for &'__a (dyn ($($trait_)+ <$($param_types)*>) $($auto)* + 'static)]
+                types [$($forall_types),*]
+                where [$($preds)*]
+                [{
+                    fn item_type_id(&self) -> $crate::__private::TypeId {
+                        $crate::Downcast::concrete_type_id(&**self)
+                    }
+                }]
+        }
+        $crate::impl_downcast! {
+            @inject_where
+                [#[allow(unused_parens)] impl<'__a, $($generics)*> $crate::iter::DowncastRefItem<'__a>
+                    for &'__a (dyn ($($trait_)+ <$($param_types)*>) $($auto)* + 'static)]
+                types [$($forall_types),*]
+                where [$($preds)*]
+                [{
+                    fn into_any_ref(self) -> &'__a dyn $crate::__private::Any {
+                        $crate::Downcast::as_any(self)
+                    }
+                }]
+        }
+        $crate::impl_downcast! {
+            @inject_where
+                [#[allow(unused_parens)] impl<'__a, $($generics)*> $crate::iter::DowncastItem
+                    for &'__a mut (dyn ($($trait_)+ <$($param_types)*>) $($auto)* + 'static)]
+                types [$($forall_types),*]
+                where [$($preds)*]
+                [{
+                    fn item_type_id(&self) -> $crate::__private::TypeId {
+                        $crate::Downcast::concrete_type_id(&**self)
+                    }
+                }]
+        }
+        $crate::impl_downcast! {
+            @inject_where
+                [#[allow(unused_parens)] impl<'__a, $($generics)*> $crate::iter::DowncastMutItem<'__a>
+                    for &'__a mut (dyn ($($trait_)+ <$($param_types)*>) $($auto)* + 'static)]
+                types [$($forall_types),*]
+                where [$($preds)*]
+                [{
+                    fn into_any_mut(self) -> &'__a mut dyn $crate::__private::Any {
+                        $crate::Downcast::as_any_mut(self)
+                    }
+                }]
+        }
+        $crate::__if_alloc! {
+            $crate::impl_downcast! {
+                @inject_where
+                    [#[allow(unused_parens)] impl<'__a, $($generics)*> $crate::iter::DowncastItem
+                        for &'__a $crate::__private::Box<dyn ($($trait_)+ <$($param_types)*>) $($auto)*>]
+                    types [$($forall_types),*]
+                    where [$($preds)*]
+                    [{
+                        fn item_type_id(&self) -> $crate::__private::TypeId {
+                            $crate::Downcast::concrete_type_id(&***self)
+                        }
+                    }]
+            }
+            $crate::impl_downcast! {
+                @inject_where
+                    [#[allow(unused_parens)] impl<'__a, $($generics)*> $crate::iter::DowncastRefItem<'__a>
+                        for &'__a $crate::__private::Box<dyn ($($trait_)+ <$($param_types)*>) $($auto)*>]
+                    types [$($forall_types),*]
+                    where [$($preds)*]
+                    [{
+                        fn into_any_ref(self) -> &'__a dyn $crate::__private::Any {
+                            $crate::Downcast::as_any(&**self)
+                        }
+                    }]
+            }
+            $crate::impl_downcast! {
+                @inject_where
+                    [#[allow(unused_parens)] impl<'__a, $($generics)*> $crate::iter::DowncastItem
+                        for &'__a mut $crate::__private::Box<dyn ($($trait_)+ <$($param_types)*>) $($auto)*>]
+                    types [$($forall_types),*]
+                    where [$($preds)*]
+                    [{
+                        fn item_type_id(&self) -> $crate::__private::TypeId {
+                            $crate::Downcast::concrete_type_id(&***self)
+                        }
+                    }]
+            }
+            $crate::impl_downcast! {
+                @inject_where
+                    [#[allow(unused_parens)] impl<'__a, $($generics)*> $crate::iter::DowncastMutItem<'__a>
+                        for &'__a mut $crate::__private::Box<dyn ($($trait_)+ <$($param_types)*>) $($auto)*>]
+                    types [$($forall_types),*]
+                    where [$($preds)*]
+                    [{
+                        fn into_any_mut(self) -> &'__a mut dyn $crate::__private::Any {
+                            $crate::Downcast::as_any_mut(&mut **self)
+                        }
+                    }]
+            }
+            $crate::impl_downcast! {
+                @inject_where
+                    [#[allow(unused_parens)] impl<$($generics)*> $crate::iter::DowncastItem
+                        for $crate::__private::Box<dyn ($($trait_)+ <$($param_types)*>) $($auto)*>]
+                    types [$($forall_types),*]
+                    where [$($preds)*]
+                    [{
+                        fn item_type_id(&self) -> $crate::__private::TypeId {
+                            $crate::Downcast::concrete_type_id(&**self)
+                        }
+                    }]
+            }
+            $crate::impl_downcast! {
+                @inject_where
+                    [#[allow(unused_parens)] impl<$($generics)*> $crate::iter::DowncastBoxItem
+                        for $crate::__private::Box<dyn ($($trait_)+ <$($param_types)*>) $($auto)*>]
+                    types [$($forall_types),*]
+                    where [$($preds)*]
+                    [{
+                        fn into_any_box(self) -> $crate::__private::Box<dyn $crate::__private::Any> {
+                            $crate::Downcast::into_any(self)
+                        }
+                    }]
+            }
+        }
+    };
+    
+    // Appends the '_T' parameter to a generics list that may be empty or end in a comma, then
+    // continues with '$($before)* [generics] $($after)*'.
+    (@append_param [$($before:tt)*] [$($after:tt)*] [$($generics:tt)*]) => {
+        $crate::impl_downcast! {$($before)* [_T] $($after)*}
+    };
+    (@append_param [$($before:tt)*] [$($after:tt)*] [$($generics:tt)*] ,) => {
+        $crate::impl_downcast! {$($before)* [$($generics)*, _T] $($after)*}
+    };
+    (@append_param [$($before:tt)*] [$($after:tt)*] [$($generics:tt)*] $last:tt) => {
+        $crate::impl_downcast! {$($before)* [$($generics)* $last, _T] $($after)*}
+    };
+    (@append_param [$($before:tt)*] [$($after:tt)*] [$($generics:tt)*] $next:tt $($rest:tt)+) => {
+        $crate::impl_downcast! {@append_param [$($before)*] [$($after)*] [$($generics)* $next] $($rest)+}
+    };
+    
+    (@impl_body [$($trait_:tt)+] [$($types:tt)*]) => {
         #[inline]
-        pub fn is<_T: $trait_<$($types),*>>(&self) -> bool {
-            crate::Downcast::as_any(self).is::<_T>()
+        pub fn is<_T: $($trait_)+ <$($types)*>>(&self) -> bool {
+            $crate::Downcast::as_any(self).is::<_T>()
         }
         
         #[inline]
-        pub fn downcast_ref<_T: $trait_<$($types),*>>(&self) -> Option<&_T> {
-            crate::Downcast::as_any(self).downcast_ref::<_T>()
+        pub fn downcast_ref<_T: $($trait_)+ <$($types)*>>(&self) -> $crate::__private::Option<&_T> {
+            $crate::Downcast::as_any(self).downcast_ref::<_T>()
         }
         
         #[inline]
-        pub fn downcast_mut<_T: $trait_<$($types),*>>(&mut self) -> Option<&mut _T> {
-            crate::Downcast::as_any_mut(self).downcast_mut::<_T>()
+        pub fn downcast_mut<_T: $($trait_)+ <$($types)*>>(&mut self) -> $crate::__private::Option<&mut _T> {
+            $crate::Downcast::as_any_mut(self).downcast_mut::<_T>()
+        }
+        
+        #[inline]
+        pub fn try_downcast_ref<_T: $($trait_)+ <$($types)*>>(
+            &self
+        ) -> $crate::__private::Result<&_T, $crate::DowncastError> {
+            match self.downcast_ref::<_T>() {
+                $crate::__private::Option::Some(value) => $crate::__private::Result::Ok(value),
+                $crate::__private::Option::None => $crate::__private::Result::Err($crate::DowncastError::new(
+                    $crate::__private::type_name::<_T>(),
+                    $crate::Downcast::concrete_type_name(self),
+                    (),
+                )),
+            }
+        }
+        
+        #[inline]
+        pub fn try_downcast_mut<_T: $($trait_)+ <$($types)*>>(
+            &mut self
+        ) -> $crate::__private::Result<&mut _T, $crate::DowncastError> {
+            if self.is::<_T>() {
+                $crate::__private::Result::Ok(self.downcast_mut::<_T>().unwrap())
+            } else {
+                $crate::__private::Result::Err($crate::DowncastError::new(
+                    $crate::__private::type_name::<_T>(),
+                    $crate::Downcast::concrete_type_name(self),
+                    (),
+                ))
+            }
+        }
+        
+        /// Returns a reference to the object as '_T' without checking its type.
+        ///
+        /// # Safety
+        ///
+        /// The trait object must wrap an object of type '_T'; debug builds assert this.
+        #[inline]
+        pub unsafe fn downcast_ref_unchecked<_T: $($trait_)+ <$($types)*>>(&self) -> &_T {
+            debug_assert!(self.is::<_T>());
+            unsafe { &*(self as *const Self as *const _T) }
+        }
+        
+        /// Returns a mutable reference to the object as '_T' without checking its type.
+        ///
+        /// # Safety
+        ///
+        /// The trait object must wrap an object of type '_T'; debug builds assert this.
+        #[inline]
+        pub unsafe fn downcast_mut_unchecked<_T: $($trait_)+ <$($types)*>>(&mut self) -> &mut _T {
+            debug_assert!(self.is::<_T>());
+            unsafe { &mut *(self as *mut Self as *mut _T) }
+        }
+        
+        // Support for 'downcast_match!', which reads the concrete type once and compares it against
+        // the type of each arm, bounded by the trait so that arms cannot name unrelated types.
+        #[doc(hidden)]
+        #[inline]
+        pub fn __downcast_match_id<_T: $($trait_)+ <$($types)*>>(&self) -> $crate::__private::TypeId {
+            $crate::__private::TypeId::of::<_T>()
+        }
+        
+        #[doc(hidden)]
+        #[inline]
+        pub fn __downcast_match_ref(&self) -> ($crate::__private::TypeId, &Self) {
+            ($crate::Downcast::concrete_type_id(self), self)
+        }
+        
+        #[doc(hidden)]
+        #[inline]
+        pub fn __downcast_match_mut(&mut self) -> ($crate::__private::TypeId, &mut Self) {
+            ($crate::Downcast::concrete_type_id(self), self)
+        }
+        
+        $crate::__if_alloc! {
+            #[doc(hidden)]
+            #[inline]
+            pub fn __downcast_match_box(
+                self: $crate::__private::Box<Self>
+            ) -> ($crate::__private::TypeId, $crate::__private::Box<Self>) {
+                ($crate::Downcast::concrete_type_id(&*self), self)
+            }
+            
+            #[inline]
+            pub fn downcast<_T: $($trait_)+ <$($types)*>>(
+                self: $crate::__private::Box<Self>
+            ) -> $crate::__private::Result<$crate::__private::Box<_T>, $crate::__private::Box<Self>> {
+                if self.is::<_T>() {
+                    $crate::__private::Result::Ok($crate::Downcast::into_any(self).downcast::<_T>().unwrap())
+                } else {
+                    $crate::__private::Result::Err(self)
+                }
+            }
+            
+            #[inline]
+            pub fn try_downcast<_T: $($trait_)+ <$($types)*>>(
+                self: $crate::__private::Box<Self>
+            ) -> $crate::__private::Result<
+                $crate::__private::Box<_T>,
+                $crate::DowncastError<$crate::__private::Box<Self>>,
+            > {
+                let found = $crate::Downcast::concrete_type_name(&*self);
+                self.downcast::<_T>().map_err(|original| {
+                    $crate::DowncastError::new($crate::__private::type_name::<_T>(), found, original)
+                })
+            }
+            
+            /// Returns the boxed object as '_T' without checking its type.
+            ///
+            /// # Safety
+            ///
+            /// The trait object must wrap an object of type '_T'; debug builds assert this.
+            #[inline]
+            pub unsafe fn downcast_unchecked<_T: $($trait_)+ <$($types)*>>(
+                self: $crate::__private::Box<Self>
+            ) -> $crate::__private::Box<_T> {
+                debug_assert!(self.is::<_T>());
+                unsafe { $crate::__private::Box::from_raw($crate::__private::Box::into_raw(self) as *mut _T) }
+            }
+            
+            #[inline]
+            pub fn downcast_rc<_T: $($trait_)+ <$($types)*>>(
+                self: $crate::__private::Rc<Self>
+            ) -> $crate::__private::Result<$crate::__private::Rc<_T>, $crate::__private::Rc<Self>> {
+                if self.is::<_T>() {
+                    $crate::__private::Result::Ok($crate::Downcast::into_any_rc(self).downcast::<_T>().unwrap())
+                } else {
+                    $crate::__private::Result::Err(self)
+                }
+            }
+            
+            #[inline]
+            pub fn try_downcast_rc<_T: $($trait_)+ <$($types)*>>(
+                self: $crate::__private::Rc<Self>
+            ) -> $crate::__private::Result<
+                $crate::__private::Rc<_T>,
+                $crate::DowncastError<$crate::__private::Rc<Self>>,
+            > {
+                let found = $crate::Downcast::concrete_type_name(&*self);
+                self.downcast_rc::<_T>().map_err(|original| {
+                    $crate::DowncastError::new($crate::__private::type_name::<_T>(), found, original)
+                })
+            }
+        }
+        
+        $crate::__if_std! {
+            /// Views the object as another trait object '_T', if its concrete type was registered
+            /// for '_T' with 'register_cast!'.
+            #[inline]
+            pub fn cast_ref<_T: ?Sized + 'static>(&self) -> $crate::__private::Option<&_T> {
+                $crate::cast::cast_ref::<_T>($crate::Downcast::as_any(self))
+            }
+            
+            #[inline]
+            pub fn cast_mut<_T: ?Sized + 'static>(&mut self) -> $crate::__private::Option<&mut _T> {
+                $crate::cast::cast_mut::<_T>($crate::Downcast::as_any_mut(self))
+            }
+            
+            #[inline]
+            pub fn cast_box<_T: ?Sized + 'static>(
+                self: $crate::__private::Box<Self>
+            ) -> $crate::__private::Result<$crate::__private::Box<_T>, $crate::__private::Box<Self>> {
+                $crate::cast::cast_box::<_T, Self>(self)
+            }
+            
+            /// Returns a reference to the object, or to the value inside the wrappers registered
+            /// with 'wrapper::register', if it is of type '_T', along with the number of wrappers
+            /// around it.
+            #[inline]
+            pub fn downcast_ref_inner<_T: $crate::__private::Any>(
+                &self
+            ) -> $crate::__private::Option<(&_T, usize)> {
+                $crate::wrapper::downcast_ref_inner::<_T>($crate::Downcast::as_any(self))
+            }
+            
+            #[inline]
+            pub fn downcast_mut_inner<_T: $crate::__private::Any>(
+                &mut self
+            ) -> $crate::__private::Option<(&mut _T, usize)> {
+                $crate::wrapper::downcast_mut_inner::<_T>($crate::Downcast::as_any_mut(self))
+            }
+        }
+    };
+    
+    (@impl_body_sync [$($trait_:tt)+] [$($types:tt)*]) => {
+        $crate::__if_alloc! {
+            #[inline]
+            pub fn downcast_arc<_T: $($trait_)+ <$($types)*>>(
+                self: $crate::__private::Arc<Self>
+            ) -> $crate::__private::Result<$crate::__private::Arc<_T>, $crate::__private::Arc<Self>> {
+                if self.is::<_T>() {
+                    $crate::__private::Result::Ok(
+                        $crate::DowncastSync::into_any_arc(self).downcast::<_T>().unwrap()
+                    )
+                } else {
+                    $crate::__private::Result::Err(self)
+                }
+            }
+            
+            #[inline]
+            pub fn try_downcast_arc<_T: $($trait_)+ <$($types)*>>(
+                self: $crate::__private::Arc<Self>
+            ) -> $crate::__private::Result<
+                $crate::__private::Arc<_T>,
+                $crate::DowncastError<$crate::__private::Arc<Self>>,
+            > {
+                let found = $crate::Downcast::concrete_type_name(&*self);
+                self.downcast_arc::<_T>().map_err(|original| {
+                    $crate::DowncastError::new($crate::__private::type_name::<_T>(), found, original)
+                })
+            }
         }
     };
     
     (@inject_where [$($before:tt)*] types [] where [] [$($after:tt)*]) => {
-	    impl_downcast! {@as_item $($before)* $($after)*}
+	    $crate::impl_downcast! {@as_item $($before)* $($after)*}
     };
     
     (@inject_where [$($before:tt)*] types [$($types:ident),*] where [] [$($after:tt)*]) =>{
-        impl_downcast! {
+        $crate::impl_downcast! {
             @as_item
                 $($before)*
-                where $($types: ::std::any::Any + 'static),*
+                where $($types: $crate::__private::Any + 'static),*
                 $($after)*
         }
     };
     
     (@inject_where [$($before:tt)*] types [$($types:ident),*] where [$($preds:tt)+] [$($after:tt)*]) => {
-	    impl_downcast! {
+	    $crate::impl_downcast! {
             @as_item
                 $($before)*
             where
-                $($types: ::std::any::Any + 'static,)*
+                $($types: $crate::__private::Any + 'static,)*
                 $($preds)*
             $($after)*
         }
@@ -78,35 +1318,378 @@ macro_rules! impl_downcast {
 
     (@as_item $i:item) => {$i};
     
-    // No type parameters.
-    ($trait_:ident) => {impl_downcast! {@impl_full $trait_ [] for [] where []}};
-    ($trait_:ident <>) => {impl_downcast! {@impl_full $trait_ [] for [] where []}};
-    // Type parameters.
-    ($trait_:ident < $($types:ident),*>) => {
-        impl_downcast! {@impl_full $trait_ [$($types),*] for [$($types),*] where []}
+    // Munches generic arguments: lifetimes, type parameters, const parameters and associated types
+    // bound to type parameters. Only the type parameters are bounded by 'Any'; lifetimes must
+    // outlive 'static for the trait object to be 'Any' at all.
+    (@generic [$($full:tt)+] [$($trait_:tt)+] < $($args:tt)*) => {
+        $crate::impl_downcast! {@generic [$($full)+] [$($trait_)+] [] [] [] [] $($args)*}
+    };
+    (@generic [$($full:tt)+] [$($trait_:tt)+]
+        [$($params:tt)*] [$($generics:tt)*] [$($types:ident)*] [$($lifetimes:lifetime)*]
+        $lifetime:lifetime , $($rest:tt)*
+    ) => {
+        $crate::impl_downcast! {
+            @generic [$($full)+] [$($trait_)+]
+                [$($params)* $lifetime,] [$($generics)* $lifetime,]
+                [$($types)*] [$($lifetimes)* $lifetime]
+                $($rest)*
+        }
+    };
+    (@generic [$($full:tt)+] [$($trait_:tt)+]
+        [$($params:tt)*] [$($generics:tt)*] [$($types:ident)*] [$($lifetimes:lifetime)*]
+        $lifetime:lifetime > $($rest:tt)*
+    ) => {
+        $crate::impl_downcast! {
+            @generic [$($full)+] [$($trait_)+]
+                [$($params)* $lifetime] [$($generics)* $lifetime]
+                [$($types)*] [$($lifetimes)* $lifetime]
+                > $($rest)*
+        }
+    };
+    (@generic [$($full:tt)+] [$($trait_:tt)+]
+        [$($params:tt)*] [$($generics:tt)*] [$($types:ident)*] [$($lifetimes:lifetime)*]
+        const $const_:ident : $const_type:ty , $($rest:tt)*
+    ) => {
+        $crate::impl_downcast! {
+            @generic [$($full)+] [$($trait_)+]
+                [$($params)* $const_,] [$($generics)* const $const_: $const_type,]
+                [$($types)*] [$($lifetimes)*]
+                $($rest)*
+        }
+    };
+    (@generic [$($full:tt)+] [$($trait_:tt)+]
+        [$($params:tt)*] [$($generics:tt)*] [$($types:ident)*] [$($lifetimes:lifetime)*]
+        const $const_:ident : $const_type:ty > $($rest:tt)*
+    ) => {
+        $crate::impl_downcast! {
+            @generic [$($full)+] [$($trait_)+]
+                [$($params)* $const_] [$($generics)* const $const_: $const_type]
+                [$($types)*] [$($lifetimes)*]
+                > $($rest)*
+        }
+    };
+    (@generic [$($full:tt)+] [$($trait_:tt)+]
+        [$($params:tt)*] [$($generics:tt)*] [$($types:ident)*] [$($lifetimes:lifetime)*]
+        $assoc:ident = $type_:ident , $($rest:tt)*
+    ) => {
+        $crate::impl_downcast! {
+            @generic [$($full)+] [$($trait_)+]
+                [$($params)* $assoc = $type_,] [$($generics)* $type_,]
+                [$($types)* $type_] [$($lifetimes)*]
+                $($rest)*
+        }
+    };
+    (@generic [$($full:tt)+] [$($trait_:tt)+]
+        [$($params:tt)*] [$($generics:tt)*] [$($types:ident)*] [$($lifetimes:lifetime)*]
+        $assoc:ident = $type_:ident > $($rest:tt)*
+    ) => {
+        $crate::impl_downcast! {
+            @generic [$($full)+] [$($trait_)+]
+                [$($params)* $assoc = $type_] [$($generics)* $type_]
+                [$($types)* $type_] [$($lifetimes)*]
+                > $($rest)*
+        }
+    };
+    (@generic [$($full:tt)+] [$($trait_:tt)+]
+        [$($params:tt)*] [$($generics:tt)*] [$($types:ident)*] [$($lifetimes:lifetime)*]
+        $type_:ident , $($rest:tt)*
+    ) => {
+        $crate::impl_downcast! {
+            @generic [$($full)+] [$($trait_)+]
+                [$($params)* $type_,] [$($generics)* $type_,]
+                [$($types)* $type_] [$($lifetimes)*]
+                $($rest)*
+        }
+    };
+    (@generic [$($full:tt)+] [$($trait_:tt)+]
+        [$($params:tt)*] [$($generics:tt)*] [$($types:ident)*] [$($lifetimes:lifetime)*]
+        $type_:ident > $($rest:tt)*
+    ) => {
+        $crate::impl_downcast! {
+            @generic [$($full)+] [$($trait_)+]
+                [$($params)* $type_] [$($generics)* $type_]
+                [$($types)* $type_] [$($lifetimes)*]
+                > $($rest)*
+        }
+    };
+    (@generic [$($full:tt)+] [$($trait_:tt)+]
+        [$($params:tt)*] [$($generics:tt)*] [$($types:ident)*] [$($lifetimes:lifetime)*] >
+    ) => {
+        $crate::impl_downcast! {
+            $($full)+ [$($trait_)+] [$($params)*] for [$($generics)*] types [$($types),*]
+                where [$($lifetimes: 'static),*]
+        }
+    };
+    (@generic [$($full:tt)+] [$($trait_:tt)+]
+        [$($params:tt)*] [$($generics:tt)*] [$($types:ident)*] [$($lifetimes:lifetime)*]
+        > where $($preds:tt)+
+    ) => {
+        $crate::impl_downcast! {
+            $($full)+ [$($trait_)+] [$($params)*] for [$($generics)*] types [$($types),*]
+                where [$($lifetimes: 'static,)* $($preds)*]
+        }
+    };
+    
+    // Munches concrete generic arguments: lifetimes, types, const values and associated types bound
+    // to types.
+    (@concrete [$($full:tt)+] [$($trait_:tt)+] < $($args:tt)*) => {
+        $crate::impl_downcast! {@concrete [$($full)+] [$($trait_)+] [] $($args)*}
+    };
+    (@concrete [$($full:tt)+] [$($trait_:tt)+] [$($params:tt)*] >) => {
+        $crate::impl_downcast! {$($full)+ [$($trait_)+] [$($params)*] for [] types [] where []}
+    };
+    (@concrete [$($full:tt)+] [$($trait_:tt)+] [$($params:tt)*] $lifetime:lifetime , $($rest:tt)*) => {
+        $crate::impl_downcast! {@concrete [$($full)+] [$($trait_)+] [$($params)* $lifetime,] $($rest)*}
+    };
+    (@concrete [$($full:tt)+] [$($trait_:tt)+] [$($params:tt)*] $lifetime:lifetime >) => {
+        $crate::impl_downcast! {@concrete [$($full)+] [$($trait_)+] [$($params)* $lifetime] >}
+    };
+    (@concrete [$($full:tt)+] [$($trait_:tt)+] [$($params:tt)*] $value:literal , $($rest:tt)*) => {
+        $crate::impl_downcast! {@concrete [$($full)+] [$($trait_)+] [$($params)* $value,] $($rest)*}
+    };
+    (@concrete [$($full:tt)+] [$($trait_:tt)+] [$($params:tt)*] $value:literal >) => {
+        $crate::impl_downcast! {@concrete [$($full)+] [$($trait_)+] [$($params)* $value] >}
+    };
+    (@concrete [$($full:tt)+] [$($trait_:tt)+] [$($params:tt)*] {$($value:tt)*} , $($rest:tt)*) => {
+        $crate::impl_downcast! {@concrete [$($full)+] [$($trait_)+] [$($params)* {$($value)*},] $($rest)*}
+    };
+    (@concrete [$($full:tt)+] [$($trait_:tt)+] [$($params:tt)*] {$($value:tt)*} >) => {
+        $crate::impl_downcast! {@concrete [$($full)+] [$($trait_)+] [$($params)* {$($value)*}] >}
     };
-    // Type parameters and where clauses.
-    ($trait_:ident <$($types:ident),*> where $($preds:tt)+) => {
-        impl_downcast! {@impl_full $trait_ [$($types),*] for [$($types),*] where [$($preds)*]}
+    (@concrete [$($full:tt)+] [$($trait_:tt)+] [$($params:tt)*] $assoc:ident = $type_:ty , $($rest:tt)*) => {
+        $crate::impl_downcast! {@concrete [$($full)+] [$($trait_)+] [$($params)* $assoc = $type_,] $($rest)*}
     };
-    // Concretely-parametrized types.
-    (concrete $trait_:ident <$($types:ident),*>) => {
-        impl_downcast! {@impl_full $trait_ [$($types),*] for [] where[]}
+    (@concrete [$($full:tt)+] [$($trait_:tt)+] [$($params:tt)*] $assoc:ident = $type_:ty >) => {
+        $crate::impl_downcast! {@concrete [$($full)+] [$($trait_)+] [$($params)* $assoc = $type_] >}
     };
+    (@concrete [$($full:tt)+] [$($trait_:tt)+] [$($params:tt)*] $type_:ty , $($rest:tt)*) => {
+        $crate::impl_downcast! {@concrete [$($full)+] [$($trait_)+] [$($params)* $type_,] $($rest)*}
+    };
+    (@concrete [$($full:tt)+] [$($trait_:tt)+] [$($params:tt)*] $type_:ty >) => {
+        $crate::impl_downcast! {@concrete [$($full)+] [$($trait_)+] [$($params)* $type_] >}
+    };
+    
+    // Munches the trait path, which is followed by optional generic arguments.
+    (@path [$($next:tt)+] [$($path:tt)*] :: $segment:ident $($rest:tt)*) => {
+        $crate::impl_downcast! {@path [$($next)+] [$($path)* :: $segment] $($rest)*}
+    };
+    (@path [$($next:tt)+] [] $segment:ident $($rest:tt)*) => {
+        $crate::impl_downcast! {@path [$($next)+] [$segment] $($rest)*}
+    };
+    (@path [$($next:tt)+] [$($path:tt)+]) => {
+        $crate::impl_downcast! {$($next)+ [$($path)+] <>}
+    };
+    (@path [$($next:tt)+] [$($path:tt)+] < $($rest:tt)*) => {
+        $crate::impl_downcast! {$($next)+ [$($path)+] < $($rest)*}
+    };
+    
+    // Collects the options placed before the trait. The second list holds the strongest equality
+    // required, 'partial_eq' or 'total_eq', so that combined options implement 'PartialEq' and
+    // 'Eq' once. A keyword followed by '::' is the first segment of the trait path instead, unless
+    // the keywords are ended with ';'.
+    (@options [$($options:tt)*] [$($eq:tt)*] sync concrete ; $($rest:tt)+) => {
+        $crate::impl_downcast! {@path [@concrete [@impl_full_sync [$($options)* $($eq)*]]] [] $($rest)+}
+    };
+    (@options [$($options:tt)*] [$($eq:tt)*] sync ; $($rest:tt)+) => {
+        $crate::impl_downcast! {@path [@generic [@impl_full_sync [$($options)* $($eq)*]]] [] $($rest)+}
+    };
+    (@options [$($options:tt)*] [$($eq:tt)*] concrete ; $($rest:tt)+) => {
+        $crate::impl_downcast! {@path [@concrete [@impl_full [$($options)* $($eq)*]]] [] $($rest)+}
+    };
+    (@options [$($options:tt)*] [$($eq:tt)*] ; $($rest:tt)+) => {
+        $crate::impl_downcast! {@path [@generic [@impl_full [$($options)* $($eq)*]]] [] $($rest)+}
+    };
+    (@options [$($options:tt)*] [$($eq:tt)*] $segment:ident :: $($rest:tt)+) => {
+        $crate::impl_downcast! {
+            @path [@generic [@impl_full [$($options)* $($eq)*]]] [] $segment :: $($rest)+
+        }
+    };
+    (@options [$($options:tt)*] [$($eq:tt)*] clone $($rest:tt)+) => {
+        $crate::impl_downcast! {@options [$($options)* clone] [$($eq)*] $($rest)+}
+    };
+    (@options [$($options:tt)*] [$($eq:tt)*] debug $($rest:tt)+) => {
+        $crate::impl_downcast! {@options [$($options)* debug] [$($eq)*] $($rest)+}
+    };
+    (@options [$($options:tt)*] [$($eq:tt)*] error $($rest:tt)+) => {
+        $crate::impl_downcast! {@options [$($options)* error] [$($eq)*] $($rest)+}
+    };
+    (@options [$($options:tt)*] [] eq $($rest:tt)+) => {
+        $crate::impl_downcast! {@options [$($options)*] [partial_eq] $($rest)+}
+    };
+    (@options [$($options:tt)*] [$($eq:tt)+] eq $($rest:tt)+) => {
+        $crate::impl_downcast! {@options [$($options)*] [$($eq)+] $($rest)+}
+    };
+    (@options [$($options:tt)*] [$($eq:tt)*] hash $($rest:tt)+) => {
+        $crate::impl_downcast! {@options [$($options)* hash] [total_eq] $($rest)+}
+    };
+    (@options [$($options:tt)*] [$($eq:tt)*] ord ($key:path) $($rest:tt)+) => {
+        $crate::impl_downcast! {@options [$($options)* ord [$key]] [total_eq] $($rest)+}
+    };
+    (@options [$($options:tt)*] [$($eq:tt)*] ord $($rest:tt)+) => {
+        compile_error!("the 'ord' option needs a key ordering the concrete types, as in 'ord(key)'");
+    };
+    
+    // Thread-safe traits, with the same forms as below.
+    (@options [$($options:tt)*] [$($eq:tt)*] sync concrete $($rest:tt)+) => {
+        $crate::impl_downcast! {@path [@concrete [@impl_full_sync [$($options)* $($eq)*]]] [] $($rest)+}
+    };
+    (@options [$($options:tt)*] [$($eq:tt)*] sync $($rest:tt)+) => {
+        $crate::impl_downcast! {@path [@generic [@impl_full_sync [$($options)* $($eq)*]]] [] $($rest)+}
+    };
+    
+    // Concretely-parametrized types and associated types.
+    (@options [$($options:tt)*] [$($eq:tt)*] concrete $($rest:tt)+) => {
+        $crate::impl_downcast! {@path [@concrete [@impl_full [$($options)* $($eq)*]]] [] $($rest)+}
+    };
+    // Optional type parameters and associated types, optionally followed by where clauses.
+    (@options [$($options:tt)*] [$($eq:tt)*] $($rest:tt)+) => {
+        $crate::impl_downcast! {@path [@generic [@impl_full [$($options)* $($eq)*]]] [] $($rest)+}
+    };
+    
+    ($($rest:tt)+) => {
+        $crate::impl_downcast! {@options [] [] $($rest)+}
+    };
+}
+
+/// Dispatches on the concrete type behind a trait object declared with 'impl_downcast!', reading
+/// its type once. Arms bind the object as the named type and a fallback arm, '_' or a name bound
+/// to the trait object itself, is required:
+///
+/// ```
+/// #[macro_use]
+/// extern crate wzDowncast;
+/// use wzDowncast::prelude::*;
+///
+/// trait Shape: Downcast {}
+/// impl_downcast!(Shape);
+/// struct Circle(f64);
+/// impl Shape for Circle {}
+/// struct Square(f64);
+/// impl Shape for Square {}
+///
+/// fn main() {
+///     let mut circle = Circle(1.0);
+///     let shape: &mut dyn Shape = &mut circle;
+///     let area = downcast_match!(shape,
+///         circle: Circle => 3.0 * circle.0 * circle.0,
+///         square: Square => square.0 * square.0,
+///         _ => 0.0,
+///     );
+///     assert_eq!(area, 3.0);
+///
+///     downcast_match!(mut shape, circle: Circle => circle.0 = 2.0, _ => {});
+///     assert_eq!(circle.0, 2.0);
+/// }
+/// ```
+///
+/// 'mut' binds mutable references, and 'box', given a 'Box<dyn Trait>', binds boxes.
+///
+/// ```compile_fail
+/// # #[macro_use]
+/// # extern crate wzDowncast;
+/// # trait Shape: wzDowncast::Downcast {}
+/// # impl_downcast!(Shape);
+/// # struct Circle(f64);
+/// # impl Shape for Circle {}
+/// # fn main() {
+/// let shape: Box<dyn Shape> = Box::new(Circle(1.0));
+/// downcast_match!(shape, circle: Circle => circle.0);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! downcast_match {
+    (@arms [$($mode:tt)+] $id:ident $value:ident $bind:tt : $type_:ty => $body:expr , $($rest:tt)+) => {
+        if $id == $value.__downcast_match_id::<$type_>() {
+            #[allow(unused_variables)]
+            let $bind = $crate::downcast_match!(@cast [$($mode)+] $value $type_);
+            $body
+        } else {
+            $crate::downcast_match!(@arms [$($mode)+] $id $value $($rest)+)
+        }
+    };
+    // Like match arms, block bodies may omit the trailing comma.
+    (@arms [$($mode:tt)+] $id:ident $value:ident $bind:tt : $type_:ty => $body:block $($rest:tt)+) => {
+        $crate::downcast_match!(@arms [$($mode)+] $id $value $bind : $type_ => $body, $($rest)+)
+    };
+    (@arms [$($mode:tt)+] $id:ident $value:ident $bind:tt : $type_:ty => $($rest:tt)*) => {
+        compile_error!("downcast_match! requires a fallback arm")
+    };
+    (@arms [$($mode:tt)+] $id:ident $value:ident _ => $body:expr $(,)*) => {
+        $body
+    };
+    (@arms [$($mode:tt)+] $id:ident $value:ident $other:ident => $body:expr $(,)*) => {{
+        let $other = $value;
+        $body
+    }};
+    
+    // The type was compared in '@arms', so the casts below cannot change the type of the object.
+    (@cast [ref] $value:ident $type_:ty) => {
+        unsafe { &*($value as *const _ as *const $type_) }
+    };
+    (@cast [mut] $value:ident $type_:ty) => {
+        unsafe { &mut *($value as *mut _ as *mut $type_) }
+    };
+    (@cast [box] $value:ident $type_:ty) => {
+        unsafe {
+            $crate::__private::Box::from_raw($crate::__private::Box::into_raw($value) as *mut $type_)
+        }
+    };
+    
+    (mut $object:expr, $($arms:tt)+) => {{
+        let (id, value) = $object.__downcast_match_mut();
+        $crate::downcast_match!(@arms [mut] id value $($arms)+)
+    }};
+    (box $object:expr, $($arms:tt)+) => {{
+        let (id, value) = $object.__downcast_match_box();
+        $crate::downcast_match!(@arms [box] id value $($arms)+)
+    }};
+    ($object:expr, $($arms:tt)+) => {{
+        let (id, value) = $object.__downcast_match_ref();
+        $crate::downcast_match!(@arms [ref] id value $($arms)+)
+    }};
 }
 
 
-#[cfg(test)]
+#[cfg(all(test, not(feature = "alloc")))]
+mod core_test {
+    use super::Downcast;
+    
+    trait Base: Downcast {}
+    impl_downcast!(Base);
+    
+    struct Foo(u32);
+    impl Base for Foo {}
+    struct Bar;
+    impl Base for Bar {}
+    
+    #[test]
+    fn test() {
+        let mut foo = Foo(42);
+        let base: &mut dyn Base = &mut foo;
+        assert!(base.is::<Foo>());
+        assert!(base.downcast_ref::<Bar>().is_none());
+        assert!(base.try_downcast_ref::<Bar>().err().unwrap().found().ends_with("::Foo"));
+        base.downcast_mut::<Foo>().unwrap().0 = 6*9;
+        assert_eq!(base.downcast_ref::<Foo>().map(|foo| foo.0), Some(6*9));
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
 mod test {
     macro_rules! test_mod {
         (
-            $test_name:ident,
-            trait $base_trait:ty {$($base_impl:tt)*},
+            @mod $test_name:ident,
+            trait $base_trait:path {$($base_impl:tt)*},
             type $base_type:ty,
-            {$($def:tt)*}
+            {$($def:tt)*},
+            {$($extra_tests:tt)*}
         ) => {
             mod $test_name {
-                use super::super::Downcast;
+                use std::any::TypeId;
+                use std::mem;
+                use std::rc::Rc;
+                #[allow(unused_imports)]
+                use super::super::{Downcast, DowncastSync};
                 
                 // A trait that can be downcast.
                 $($def)*
@@ -131,6 +1714,16 @@ mod test {
                     }
                 }
                 
+                #[test]
+                #[cfg(debug_assertions)]
+                #[should_panic]
+                fn test_unchecked_mismatch() {
+                    let base: Box<$base_type> = Box::new(Bar(1.0));
+                    unsafe {
+                        base.downcast_ref_unchecked::<Foo>();
+                    }
+                }
+                
                 #[test]
                 fn test() {
                     let mut base: Box<$base_type> = Box::new(Foo(42));
@@ -146,17 +1739,131 @@ mod test {
                     assert_eq!(get_val(&base), 6*9);
                     
                     assert!(base.is::<Foo>());
+                    
+                    // Owned downcasting hands the original box back on failure.
+                    let base: Box<$base_type> = Box::new(Bar(19.0));
+                    let base = match base.downcast::<Foo>() {
+                        Ok(_) => panic!("downcast to the wrong type succeeded"),
+                        Err(original) => original
+                    };
+                    match base.downcast::<Bar>() {
+                        Ok(bar) => assert_eq!(bar.0, 19.0),
+                        Err(_) => panic!("downcast to the concrete type failed")
+                    }
+                    
+                    // Metadata describes the concrete type behind the trait object.
+                    let base: &$base_type = &Bar(2.0);
+                    assert!(base.concrete_type_name().ends_with("::Bar"));
+                    assert_eq!(base.concrete_type_id(), TypeId::of::<Bar>());
+                    assert_eq!(base.concrete_size(), mem::size_of::<Bar>());
+                    assert_eq!(base.concrete_align(), mem::align_of::<Bar>());
+                    assert!(!base.concrete_needs_drop());
+                    
+                    // Fallible downcasting reports both the requested and the concrete type.
+                    let mut base: Box<$base_type> = Box::new(Bar(2.0));
+                    let err = base.try_downcast_ref::<Foo>().err().unwrap();
+                    assert!(err.expected().ends_with("::Foo"));
+                    assert!(err.found().ends_with("::Bar"));
+                    assert_eq!(err.to_string(), format!("cannot downcast `{}` to `{}`", err.found(), err.expected()));
+                    assert!(::std::error::Error::source(&err).is_none());
+                    assert_eq!(base.try_downcast_mut::<Bar>().ok().unwrap().0, 2.0);
+                    let base = base.try_downcast::<Foo>().err().unwrap().into_original();
+                    assert_eq!(base.try_downcast::<Bar>().ok().unwrap().0, 2.0);
+                    let base: Rc<$base_type> = Rc::new(Bar(2.0));
+                    let base = base.try_downcast_rc::<Foo>().err().unwrap().into_original();
+                    assert!(base.try_downcast_rc::<Bar>().is_ok());
+                    
+                    // Unchecked downcasting skips the type check when the type is known.
+                    let mut base: Box<$base_type> = Box::new(Foo(3));
+                    unsafe {
+                        assert_eq!(base.downcast_ref_unchecked::<Foo>().0, 3);
+                        base.downcast_mut_unchecked::<Foo>().0 = 4;
+                        assert_eq!(base.downcast_unchecked::<Foo>().0, 4);
+                    }
+                    
+                    // Shared downcasting keeps pointing at the same allocation.
+                    let base: Rc<$base_type> = Rc::new(Foo(7));
+                    let other = base.clone();
+                    let base = match base.downcast_rc::<Bar>() {
+                        Ok(_) => panic!("downcast to the wrong type succeeded"),
+                        Err(original) => original
+                    };
+                    match base.downcast_rc::<Foo>() {
+                        Ok(foo) => {
+                            assert_eq!(foo.0, 7);
+                            assert_eq!(Rc::strong_count(&foo), 2);
+                            assert!(Rc::ptr_eq(&other, &(foo as Rc<$base_type>)));
+                        }
+                        Err(_) => panic!("downcast to the concrete type failed")
+                    }
+                }
+                
+                $($extra_tests)*
+            }
+        };
+        
+        (
+            sync $test_name:ident,
+            trait $base_trait:path {$($base_impl:tt)*},
+            {$($def:tt)+}
+        ) => {
+            test_mod! {
+                sync $test_name, trait $base_trait {$($base_impl)*}, type dyn $base_trait, {$($def)*}
+            }
+        };
+        
+        (
+            sync $test_name:ident,
+            trait $base_trait:path {$($base_impl:tt)*},
+            type $base_type:ty,
+            {$($def:tt)+}
+        ) => {
+            test_mod! {
+                @mod $test_name, trait $base_trait {$($base_impl)*}, type $base_type, {$($def)*}, {
+                    #[test]
+                    fn test_sync() {
+                        use std::sync::Arc;
+                        use std::thread;
+                        
+                        let base: Arc<$base_type> = Arc::new(Foo(42));
+                        let shared = base.clone();
+                        let val = thread::spawn(move || shared.downcast_ref::<Foo>().map(|foo| foo.0))
+                            .join()
+                            .unwrap();
+                        assert_eq!(val, Some(42));
+                        
+                        let base = match base.downcast_arc::<Bar>() {
+                            Ok(_) => panic!("downcast to the wrong type succeeded"),
+                            Err(original) => original
+                        };
+                        let base = base.try_downcast_arc::<Bar>().err().unwrap().into_original();
+                        match base.downcast_arc::<Foo>() {
+                            Ok(foo) => assert_eq!(foo.0, 42),
+                            Err(_) => panic!("downcast to the concrete type failed")
+                        }
+                    }
                 }
             }
         };
         
         (
             $test_name:ident,
-            trait $base_trait:ty {$($base_impl:tt)*},
+            trait $base_trait:path {$($base_impl:tt)*},
+            type $base_type:ty,
+            {$($def:tt)*}
+        ) => {
+            test_mod! {
+                @mod $test_name, trait $base_trait {$($base_impl)*}, type $base_type, {$($def)*}, {}
+            }
+        };
+        
+        (
+            $test_name:ident,
+            trait $base_trait:path {$($base_impl:tt)*},
             {$($def:tt)+}
         ) => {
             test_mod! {
-                $test_name, trait $base_trait {$($base_impl)*}, type $base_trait, {$($def)*}
+                @mod $test_name, trait $base_trait {$($base_impl)*}, type dyn $base_trait, {$($def)*}, {}
             }
         }
     }
@@ -180,4 +1887,918 @@ mod test {
         trait Base<T>: Downcast {}
         impl_downcast!(concrete Base<u32>);
     });
-}
\ No newline at end of file
+    
+    test_mod!(sync sync_non_generic, trait Base {}, {
+        trait Base: DowncastSync {}
+        impl_downcast!(sync Base);
+    });
+    
+    test_mod!(sync sync_constrained_generic, trait Base<u32> {}, {
+        trait Base<T: Copy>: DowncastSync {}
+        impl_downcast!(sync Base<T> where T: Copy);
+    });
+    
+    test_mod!(sync sync_concrete_parametrized, trait Base<u32> {}, {
+        trait Base<T>: DowncastSync {}
+        impl_downcast!(sync concrete Base<u32>);
+    });
+    
+    test_mod!(associated, trait Base {type H = f32;}, type dyn Base<H = f32>, {
+        trait Base: Downcast { type H; }
+        impl_downcast!(Base<H = H>);
+    });
+    
+    test_mod!(renamed_associated, trait Base {type H = f32;}, type dyn Base<H = f32>, {
+        trait Base: Downcast { type H; }
+        impl_downcast!(Base<H = O>);
+    });
+    
+    test_mod!(generic_associated, trait Base<u32> {type H = f32;}, type dyn Base<u32, H = f32>, {
+        trait Base<T>: Downcast { type H; }
+        impl_downcast!(Base<T, H = H>);
+    });
+    
+    test_mod!(constrained_generic_associated,
+        trait Base<u32> {type H = f32;}, type dyn Base<u32, H = f32>, {
+        trait Base<T: Copy>: Downcast { type H: Copy; }
+        impl_downcast!(Base<T, H = H> where T: Copy, H: Copy);
+    });
+    
+    test_mod!(concrete_associated, trait Base {type H = Vec<u8>;}, type dyn Base<H = Vec<u8>>, {
+        trait Base: Downcast { type H; }
+        impl_downcast!(concrete Base<H = Vec<u8>>);
+    });
+    
+    test_mod!(concrete_generic_associated,
+        trait Base<u32> {type H = f32;}, type dyn Base<u32, H = f32>, {
+        trait Base<T>: Downcast { type H; }
+        impl_downcast!(concrete Base<u32, H = f32>);
+    });
+    
+    test_mod!(sync sync_associated, trait Base {type H = f32;}, type dyn Base<H = f32>, {
+        trait Base: DowncastSync { type H; }
+        impl_downcast!(sync Base<H = H>);
+    });
+    
+    test_mod!(sync sync_concrete_associated, trait Base {type H = f32;}, type dyn Base<H = f32>, {
+        trait Base: DowncastSync { type H; }
+        impl_downcast!(sync concrete Base<H = f32>);
+    });
+    
+    test_mod!(send, trait Base {}, type dyn Base + Send, {
+        trait Base: Downcast {}
+        impl_downcast!(Base);
+    });
+    
+    test_mod!(generic_sync, trait Base<u32> {}, type dyn Base<u32> + Sync, {
+        trait Base<T>: Downcast {}
+        impl_downcast!(Base<T>);
+    });
+    
+    test_mod!(send_sync, trait Base {}, type dyn Base + Send + Sync, {
+        trait Base: Downcast {}
+        impl_downcast!(Base);
+    });
+    
+    test_mod!(sync sync_send_sync, trait Base {}, type dyn Base + Send + Sync, {
+        trait Base: DowncastSync {}
+        impl_downcast!(sync Base);
+    });
+    
+    test_mod!(path, trait plugins::Base {}, {
+        mod plugins {
+            pub trait Base: super::Downcast {}
+        }
+        impl_downcast!(self::plugins::Base);
+    });
+    
+    test_mod!(super_path, trait plugins::Base<u32> {}, {
+        mod plugins {
+            pub trait Base<T>: super::Downcast {}
+        }
+        mod registry {
+            impl_downcast!(super::plugins::Base<T>);
+        }
+    });
+    
+    test_mod!(concrete_path, trait plugins::Base<u32> {}, {
+        mod plugins {
+            pub trait Base<T>: super::Downcast {}
+        }
+        impl_downcast!(concrete plugins::Base<u32>);
+    });
+    
+    test_mod!(sync sync_path, trait plugins::Base {type H = f32;}, type dyn plugins::Base<H = f32>, {
+        mod plugins {
+            pub trait Base: super::DowncastSync { type H; }
+        }
+        use self::plugins::Base as Aliased;
+        impl_downcast!(sync Aliased<H = H>);
+    });
+    
+    test_mod!(lifetime_generic, trait Base<'static, u32> {}, {
+        trait Base<'a, T>: Downcast {}
+        impl_downcast!(Base<'a, T>);
+    });
+    
+    test_mod!(const_generic, trait Buffer<16> {}, {
+        trait Buffer<const N: usize>: Downcast {}
+        impl_downcast!(Buffer<const N: usize>);
+    });
+    
+    test_mod!(constrained_mixed_generic, trait Base<'static, u32, 4> {type H = f32;},
+        type dyn Base<'static, u32, 4, H = f32>, {
+        trait Base<'a, T: Copy, const N: usize>: Downcast { type H; }
+        impl_downcast!(Base<'a, T, const N: usize, H = H> where T: Copy);
+    });
+    
+    test_mod!(concrete_lifetime, trait Base<'static, u32> {}, {
+        trait Base<'a, T>: Downcast {}
+        impl_downcast!(concrete Base<'static, u32>);
+    });
+    
+    test_mod!(concrete_const, trait Buffer<u32, 16, 2> {}, {
+        trait Buffer<T, const N: usize, const M: u8>: Downcast {}
+        impl_downcast!(concrete Buffer<u32, 16, {1 + 1}>);
+    });
+    
+    test_mod!(sync sync_const_generic, trait Buffer<'static, 16> {}, {
+        trait Buffer<'a, const N: usize>: DowncastSync {}
+        impl_downcast!(sync Buffer<'a, const N: usize>);
+    });
+    
+    test_mod!(sync sync_concrete_const, trait Buffer<16> {}, {
+        trait Buffer<const N: usize>: DowncastSync {}
+        impl_downcast!(sync concrete Buffer<16>);
+    });
+    
+    mod deref {
+        use std::any::TypeId;
+        use std::rc::Rc;
+        use std::sync::Arc;
+        use super::super::prelude::*;
+        
+        trait Base: DowncastSync {}
+        impl_downcast!(sync Base);
+        
+        struct Foo(u32);
+        impl Base for Foo {}
+        
+        #[test]
+        fn test_box() {
+            let mut base: Box<dyn Base> = Box::new(Foo(42));
+            assert!(DowncastDeref::as_any(&base).is::<Foo>());
+            assert!((*base).as_any().is::<Foo>());
+            assert!(DowncastDeref::concrete_type_name(&base).ends_with("::Foo"));
+            assert_eq!(DowncastDeref::concrete_type_id(&base), TypeId::of::<Foo>());
+            DowncastDeref::as_any_mut(&mut base).downcast_mut::<Foo>().unwrap().0 = 7;
+            assert_eq!(base.downcast_ref::<Foo>().unwrap().0, 7);
+        }
+        
+        #[test]
+        fn test_rc() {
+            let base: Rc<dyn Base> = Rc::new(Foo(42));
+            assert!(DowncastDeref::as_any(&base).is::<Foo>());
+            assert!((*base).as_any().is::<Foo>());
+            assert_eq!(DowncastDeref::concrete_size(&base), 4);
+        }
+        
+        #[test]
+        fn test_arc() {
+            let base: Arc<dyn Base> = Arc::new(Foo(42));
+            assert!(DowncastDeref::as_any(&base).is::<Foo>());
+            assert!((*base).as_any().is::<Foo>());
+            assert_eq!(DowncastDeref::concrete_align(&base), 4);
+            assert!(!DowncastDeref::concrete_needs_drop(&base));
+        }
+        
+        mod method_syntax {
+            use std::rc::Rc;
+            use std::sync::Arc;
+            use super::super::super::DowncastDeref;
+            use super::{Base, Foo};
+            
+            #[test]
+            fn test() {
+                let boxed: Box<dyn Base> = Box::new(Foo(1));
+                let rc: Rc<dyn Base> = Rc::new(Foo(2));
+                let arc: Arc<dyn Base> = Arc::new(Foo(3));
+                let reference: &dyn Base = &*boxed;
+                assert!(boxed.as_any().is::<Foo>());
+                assert!(rc.as_any().is::<Foo>());
+                assert!(arc.as_any().is::<Foo>());
+                assert!(reference.as_any().is::<Foo>());
+                assert!(arc.concrete_type_name().ends_with("::Foo"));
+            }
+        }
+    }
+    
+    mod cast {
+        use std::any::TypeId;
+        use super::super::Downcast;
+        use super::super::cast::can_cast;
+        
+        trait Component: Downcast {}
+        impl_downcast!(Component);
+        
+        trait Named {
+            fn name(&self) -> String;
+            fn rename(&mut self, name: &str);
+        }
+        
+        struct Foo(String);
+        impl Component for Foo {}
+        impl Named for Foo {
+            fn name(&self) -> String { self.0.clone() }
+            fn rename(&mut self, name: &str) { self.0 = name.into(); }
+        }
+        
+        // Implements 'Named' but is never registered for it.
+        struct Bar;
+        impl Component for Bar {}
+        impl Named for Bar {
+            fn name(&self) -> String { "bar".into() }
+            fn rename(&mut self, _: &str) {}
+        }
+        
+        #[test]
+        fn test() {
+            register_cast!(Foo => dyn Named, dyn Named + Send);
+            assert!(can_cast::<dyn Named>(TypeId::of::<Foo>()));
+            assert!(!can_cast::<dyn Named>(TypeId::of::<Bar>()));
+            
+            let mut foo: Box<dyn Component> = Box::new(Foo("foo".into()));
+            assert_eq!(foo.cast_ref::<dyn Named>().unwrap().name(), "foo");
+            assert_eq!(foo.cast_ref::<dyn Named + Send>().unwrap().name(), "foo");
+            foo.cast_mut::<dyn Named>().unwrap().rename("baz");
+            assert_eq!(foo.downcast_ref::<Foo>().unwrap().0, "baz");
+            let named: Box<dyn Named> = foo.cast_box::<dyn Named>().ok().unwrap();
+            assert_eq!(named.name(), "baz");
+            
+            let mut bar: Box<dyn Component> = Box::new(Bar);
+            assert!(bar.cast_ref::<dyn Named>().is_none());
+            assert!(bar.cast_mut::<dyn Named>().is_none());
+            let bar = bar.cast_box::<dyn Named>().err().unwrap();
+            assert!(bar.is::<Bar>());
+        }
+    }
+    
+    mod downcast_match {
+        use std::rc::Rc;
+        use super::super::Downcast;
+        
+        trait Base<T>: Downcast {}
+        impl_downcast!(Base<T>);
+        
+        struct Foo(u32);
+        impl Base<u8> for Foo {}
+        struct Bar(String);
+        impl Base<u8> for Bar {}
+        struct Baz;
+        impl Base<u8> for Baz {}
+        
+        fn describe(base: &dyn Base<u8>) -> String {
+            downcast_match!(base,
+                x: Foo => format!("foo {}", x.0),
+                x: Bar => { format!("bar {}", x.0) }
+                _ => "other".into()
+            )
+        }
+        
+        #[test]
+        fn test_ref() {
+            assert_eq!(describe(&Foo(42)), "foo 42");
+            assert_eq!(describe(&Bar("x".into())), "bar x");
+            assert_eq!(describe(&Baz), "other");
+            
+            let boxed: Box<dyn Base<u8>> = Box::new(Foo(1));
+            assert!(downcast_match!(boxed, _: Foo => true, _ => false));
+            let rc: Rc<dyn Base<u8>> = Rc::new(Foo(2));
+            let other = downcast_match!(rc, _: Bar => None, other => Some(other));
+            assert!(other.unwrap().is::<Foo>());
+        }
+        
+        #[test]
+        fn test_mut() {
+            let mut base: Box<dyn Base<u8>> = Box::new(Bar("x".into()));
+            downcast_match!(mut base,
+                x: Foo => x.0 += 1,
+                x: Bar => x.0.push('y'),
+                other => assert!(other.is::<Baz>()),
+            );
+            assert_eq!(base.downcast_ref::<Bar>().unwrap().0, "xy");
+        }
+        
+        #[test]
+        fn test_box() {
+            let base: Box<dyn Base<u8>> = Box::new(Foo(7));
+            let unboxed: Box<Foo> = downcast_match!(box base,
+                x: Foo => x,
+                x: Bar => panic!("unexpected bar {}", x.0),
+                other => panic!("unexpected {}", other.concrete_type_name()),
+            );
+            assert_eq!(unboxed.0, 7);
+            
+            let base: Box<dyn Base<u8>> = Box::new(Baz);
+            let base = downcast_match!(box base, _: Foo => None, other => Some(other)).unwrap();
+            assert!(base.is::<Baz>());
+        }
+    }
+    
+    mod type_map {
+        use std::any::Any;
+        use super::super::Downcast;
+        use super::super::type_map::{Entry, TypeMap};
+        
+        trait Resource: Downcast {
+            fn name(&self) -> String;
+        }
+        impl_downcast!(Resource);
+        
+        #[derive(Debug, Default, PartialEq)]
+        struct Foo(u32);
+        impl Resource for Foo {
+            fn name(&self) -> String { format!("foo {}", self.0) }
+        }
+        #[derive(Debug, PartialEq)]
+        struct Bar(String);
+        impl Resource for Bar {
+            fn name(&self) -> String { format!("bar {}", self.0) }
+        }
+        
+        #[test]
+        fn test_trait() {
+            let mut map = TypeMap::<dyn Resource>::new();
+            assert!(map.is_empty());
+            assert_eq!(map.insert(Foo(1)), None);
+            assert_eq!(map.insert(Foo(2)), Some(Foo(1)));
+            assert_eq!(map.insert(Bar("x".into())), None);
+            assert_eq!(map.len(), 2);
+            assert!(map.contains::<Foo>());
+            
+            assert_eq!(map.get::<Foo>(), Some(&Foo(2)));
+            map.get_mut::<Bar>().unwrap().0.push('y');
+            assert_eq!(map.get::<Bar>(), Some(&Bar("xy".into())));
+            
+            let mut names: Vec<String> = map.iter().map(|resource| resource.name()).collect();
+            names.sort();
+            assert_eq!(names, ["bar xy", "foo 2"]);
+            for resource in &mut map {
+                if let Some(foo) = resource.downcast_mut::<Foo>() {
+                    foo.0 += 1;
+                }
+            }
+            assert_eq!(map.get::<Foo>(), Some(&Foo(3)));
+            
+            assert_eq!(map.remove::<Foo>(), Some(Foo(3)));
+            assert_eq!(map.remove::<Foo>(), None);
+            assert!(map.get::<Foo>().is_none());
+            let boxed: Vec<Box<dyn Resource>> = map.into_iter().collect();
+            assert!(boxed[0].is::<Bar>());
+        }
+        
+        #[test]
+        fn test_entry() {
+            let mut map = TypeMap::<dyn Resource>::new();
+            map.entry::<Foo>().or_default().0 += 1;
+            map.entry::<Foo>().and_modify(|foo| foo.0 *= 10).or_insert(Foo(0));
+            assert_eq!(map.get::<Foo>(), Some(&Foo(10)));
+            
+            match map.entry::<Foo>() {
+                Entry::Occupied(mut entry) => {
+                    assert_eq!(entry.insert(Foo(4)), Foo(10));
+                    assert_eq!(entry.remove(), Foo(4));
+                }
+                Entry::Vacant(_) => panic!("expected an occupied entry"),
+            }
+            match map.entry::<Bar>() {
+                Entry::Vacant(entry) => entry.insert(Bar("z".into())).0.push('!'),
+                Entry::Occupied(_) => panic!("expected a vacant entry"),
+            }
+            assert_eq!(map.entry::<Bar>().or_insert_with(|| unreachable!()).0, "z!");
+            assert!(!map.contains::<Foo>());
+        }
+        
+        #[test]
+        fn test_any() {
+            let mut map = TypeMap::<dyn Any + Send + Sync>::default();
+            map.insert(1u8);
+            map.insert("text");
+            *map.entry::<u8>().or_insert(0) += 1;
+            assert_eq!(map.get::<u8>(), Some(&2));
+            assert_eq!(map.get::<&str>(), Some(&"text"));
+            assert_eq!(map.iter().filter(|value| value.is::<u8>()).count(), 1);
+            map.clear();
+            assert!(map.is_empty());
+        }
+    }
+    
+    mod sync_type_map {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::thread;
+        use super::super::DowncastSync;
+        use super::super::sync_type_map::SyncTypeMap;
+        
+        trait Service: DowncastSync {
+            fn name(&self) -> String;
+        }
+        impl_downcast!(sync Service);
+        
+        #[derive(Debug, PartialEq)]
+        struct Config(u32);
+        impl Service for Config {
+            fn name(&self) -> String { "config".into() }
+        }
+        #[derive(Debug, PartialEq)]
+        struct Database(u32);
+        impl Service for Database {
+            fn name(&self) -> String { "database".into() }
+        }
+        
+        #[test]
+        fn test() {
+            let map = SyncTypeMap::<dyn Service>::new();
+            assert!(map.is_empty());
+            assert!(map.insert(Config(1)).is_none());
+            assert_eq!(map.insert(Config(2)).as_deref(), Some(&Config(1)));
+            assert!(map.contains::<Config>());
+            assert!(!map.contains::<Database>());
+            assert_eq!(map.get::<Config>().as_deref(), Some(&Config(2)));
+            assert_eq!(map.get_erased::<Config>().unwrap().name(), "config");
+            assert!(map.get::<Database>().is_none());
+            
+            // The default may read the map itself.
+            let database = map.get_or_insert_with(|| Database(map.get::<Config>().unwrap().0 * 10));
+            assert_eq!(*database, Database(20));
+            assert_eq!(map.get_or_insert_with(|| Database(0)), database);
+            assert_eq!(map.len(), 2);
+            
+            assert_eq!(map.remove::<Config>().as_deref(), Some(&Config(2)));
+            assert!(map.remove::<Config>().is_none());
+            assert_eq!(map.len(), 1);
+        }
+        
+        #[test]
+        fn test_threads() {
+            let map = Arc::new(SyncTypeMap::<dyn Service + Send + Sync>::default());
+            let created = Arc::new(AtomicUsize::new(0));
+            let threads: Vec<_> = (0..8).map(|_| {
+                let map = map.clone();
+                let created = created.clone();
+                thread::spawn(move || {
+                    let config = map.get_or_insert_with(|| {
+                        created.fetch_add(1, Ordering::SeqCst);
+                        Config(7)
+                    });
+                    (0..100).all(|_| Arc::ptr_eq(&map.get::<Config>().unwrap(), &config))
+                })
+            }).collect();
+            for thread in threads {
+                assert!(thread.join().unwrap());
+            }
+            assert!(created.load(Ordering::SeqCst) >= 1);
+            assert_eq!(map.len(), 1);
+        }
+    }
+    
+    mod iter {
+        use super::super::Downcast;
+        use super::super::iter::DowncastIter;
+        
+        trait Base: Downcast {}
+        impl_downcast!(Base);
+        
+        #[derive(Debug, PartialEq)]
+        struct Foo(u32);
+        impl Base for Foo {}
+        #[derive(Debug, PartialEq)]
+        struct Bar;
+        impl Base for Bar {}
+        
+        fn bases() -> Vec<Box<dyn Base>> {
+            vec![Box::new(Foo(1)), Box::new(Bar), Box::new(Foo(2))]
+        }
+        
+        #[test]
+        fn test_ref() {
+            let bases = bases();
+            let foos: Vec<&Foo> = bases.iter().downcast_filter::<Foo>().collect();
+            assert_eq!(foos, [&Foo(1), &Foo(2)]);
+            assert_eq!(bases.iter().map(|base| &**base).downcast_filter::<Bar>().count(), 1);
+            assert_eq!(bases.iter().count_of::<Foo>(), 2);
+            assert_eq!(bases.iter().map(|base| &**base).count_of::<Bar>(), 1);
+            assert_eq!(bases.iter().count_of::<u32>(), 0);
+        }
+        
+        #[test]
+        fn test_mut() {
+            let mut bases = bases();
+            for foo in bases.iter_mut().downcast_filter_mut::<Foo>() {
+                foo.0 *= 10;
+            }
+            for foo in bases.iter_mut().map(|base| &mut **base).downcast_filter_mut::<Foo>() {
+                foo.0 += 1;
+            }
+            assert_eq!(bases.iter_mut().count_of::<Foo>(), 2);
+            let foos: Vec<&Foo> = bases.iter().downcast_filter::<Foo>().collect();
+            assert_eq!(foos, [&Foo(11), &Foo(21)]);
+        }
+        
+        #[test]
+        fn test_owned() {
+            assert_eq!(bases().into_iter().count_of::<Bar>(), 1);
+            let (foos, rest) = bases().into_iter().partition_downcast::<Foo>();
+            assert_eq!(foos, [Box::new(Foo(1)), Box::new(Foo(2))]);
+            assert_eq!(rest.len(), 1);
+            assert!(rest[0].is::<Bar>());
+        }
+    }
+    
+    mod clone {
+        use std::rc::Rc;
+        use std::sync::Arc;
+        use super::super::{clone_box, DowncastClone, MakeMutDowncast};
+        
+        trait Base: DowncastClone {}
+        impl_downcast!(clone Base);
+        
+        #[derive(Clone, Debug, PartialEq)]
+        struct Foo(Vec<u32>);
+        impl Base for Foo {}
+        #[derive(Clone)]
+        struct Bar;
+        impl Base for Bar {}
+        
+        #[test]
+        fn test_box() {
+            let base: Box<dyn Base> = Box::new(Foo(vec![1, 2]));
+            let mut cloned = base.clone();
+            cloned.downcast_mut::<Foo>().unwrap().0.push(3);
+            assert_eq!(base.downcast_ref::<Foo>(), Some(&Foo(vec![1, 2])));
+            assert_eq!(cloned.downcast_ref::<Foo>(), Some(&Foo(vec![1, 2, 3])));
+            
+            let reference: &dyn Base = &Bar;
+            assert!(reference.clone_box().is::<Bar>());
+            assert!(clone_box(reference).is::<Bar>());
+            assert_eq!(*clone_box(&Foo(vec![4])), Foo(vec![4]));
+        }
+        
+        #[test]
+        fn test_make_mut_rc() {
+            let mut base: Rc<dyn Base> = Rc::new(Foo(vec![1]));
+            let shared = base.clone();
+            assert!(base.make_mut_downcast::<Bar>().is_none());
+            assert!(Rc::ptr_eq(&base, &shared));
+            
+            base.make_mut_downcast::<Foo>().unwrap().0.push(2);
+            assert!(!Rc::ptr_eq(&base, &shared));
+            assert_eq!(shared.downcast_ref::<Foo>(), Some(&Foo(vec![1])));
+            assert_eq!(base.downcast_ref::<Foo>(), Some(&Foo(vec![1, 2])));
+            
+            // Unique pointers are modified in place.
+            let before = &*base as *const dyn Base as *const ();
+            base.make_mut_downcast::<Foo>().unwrap().0.push(3);
+            assert_eq!(&*base as *const dyn Base as *const (), before);
+        }
+        
+        #[test]
+        fn test_make_mut_arc() {
+            let mut base: Arc<dyn Base> = Arc::new(Foo(vec![1]));
+            let shared = base.clone();
+            base.make_mut_downcast::<Foo>().unwrap().0[0] = 2;
+            assert_eq!(shared.downcast_ref::<Foo>(), Some(&Foo(vec![1])));
+            assert_eq!(base.downcast_ref::<Foo>(), Some(&Foo(vec![2])));
+        }
+    }
+    
+    mod cmp {
+        use std::collections::hash_map::DefaultHasher;
+        use std::collections::{BTreeSet, HashSet};
+        use std::hash::{Hash, Hasher};
+        use super::super::cmp::{DynEq, DynHash, DynOrd};
+        
+        trait Key: DynHash {}
+        impl_downcast!(hash Key);
+        
+        #[derive(PartialEq, Eq, Hash)]
+        struct Foo(u32);
+        impl Key for Foo {}
+        #[derive(PartialEq, Eq, Hash)]
+        struct Bar(u32);
+        impl Key for Bar {}
+        
+        trait Shape: DynEq {}
+        impl_downcast!(eq Shape);
+        
+        #[derive(PartialEq)]
+        struct Circle(f64);
+        impl Shape for Circle {}
+        
+        fn hash(key: &dyn Key) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            key.hash(&mut hasher);
+            hasher.finish()
+        }
+        
+        #[test]
+        fn test_eq() {
+            let a: Box<dyn Key> = Box::new(Foo(1));
+            assert!(a == Box::new(Foo(1)) as Box<dyn Key>);
+            assert!(a != Box::new(Foo(2)) as Box<dyn Key>);
+            assert!(a != Box::new(Bar(1)) as Box<dyn Key>);
+            assert!(Foo(1).dyn_eq(&Foo(1)));
+            assert!(!Foo(1).dyn_eq(&1u32));
+            
+            let shape: &dyn Shape = &Circle(1.0);
+            assert!(shape == &Circle(1.0) as &dyn Shape);
+            assert!(shape != &Circle(f64::NAN) as &dyn Shape);
+        }
+        
+        #[test]
+        fn test_hash() {
+            assert_eq!(hash(&Foo(1)), hash(&Foo(1)));
+            assert_ne!(hash(&Foo(1)), hash(&Bar(1)));
+            
+            let mut set: HashSet<Box<dyn Key>> = HashSet::new();
+            assert!(set.insert(Box::new(Foo(1))));
+            assert!(set.insert(Box::new(Bar(1))));
+            assert!(!set.insert(Box::new(Foo(1))));
+            assert!(set.contains(&(Box::new(Bar(1)) as Box<dyn Key>)));
+            assert_eq!(set.len(), 2);
+        }
+        
+        // Tasks sort by priority, then within a type by the type's own order.
+        trait Task: DynHash + DynOrd {
+            fn priority(&self) -> u32;
+        }
+        impl_downcast!(eq hash ord(Task::priority) Task);
+        
+        #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+        struct Flush(u32);
+        impl Task for Flush {
+            fn priority(&self) -> u32 { 1 }
+        }
+        #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+        struct Compact(&'static str);
+        impl Task for Compact {
+            fn priority(&self) -> u32 { 0 }
+        }
+        
+        trait Sorted: DynOrd {}
+        fn same_key(_: &dyn Sorted) {}
+        impl_downcast!(ord(same_key) Sorted);
+        impl Sorted for Flush {}
+        impl Sorted for Compact {}
+        
+        #[test]
+        fn test_ord() {
+            let tasks: BTreeSet<Box<dyn Task>> = vec![
+                Box::new(Flush(2)) as Box<dyn Task>,
+                Box::new(Compact("b")),
+                Box::new(Flush(1)),
+                Box::new(Compact("a")),
+                Box::new(Flush(2)),
+            ].into_iter().collect();
+            let priorities: Vec<_> = tasks.iter().map(|task| task.priority()).collect();
+            assert_eq!(priorities, [0, 0, 1, 1]);
+            assert_eq!(tasks.iter().next().unwrap().downcast_ref::<Compact>(), Some(&Compact("a")));
+            assert_eq!(tasks.iter().last().unwrap().downcast_ref::<Flush>(), Some(&Flush(2)));
+            assert_eq!(Flush(1).dyn_cmp(&Flush(2)), Some(::std::cmp::Ordering::Less));
+            assert_eq!(Flush(1).dyn_cmp(&Compact("a")), None);
+            
+            // Types with equal keys are ordered by name.
+            let a: &dyn Sorted = &Compact("z");
+            let b: &dyn Sorted = &Flush(0);
+            assert!(a < b);
+            assert!(a == &Compact("z") as &dyn Sorted);
+        }
+    }
+    
+    mod debug {
+        use std::any::TypeId;
+        use super::super::{debug, Downcast};
+        
+        trait Base: Downcast {}
+        impl_downcast!(debug Base);
+        
+        #[derive(Debug)]
+        struct Foo(u32);
+        impl Base for Foo {}
+        struct Bar;
+        impl Base for Bar {}
+        
+        #[test]
+        fn test() {
+            debug::register::<Foo>();
+            assert!(debug::is_registered(TypeId::of::<Foo>()));
+            assert!(!debug::is_registered(TypeId::of::<Bar>()));
+            
+            let foo: Box<dyn Base> = Box::new(Foo(1));
+            assert_eq!(format!("{:?}", foo), "Foo(1)");
+            assert_eq!(foo.downcast_ref::<Foo>().map(|foo| foo.0), Some(1));
+            assert_eq!(format!("{:#?}", Some(&*foo)), "Some(\n    Foo(\n        1,\n    ),\n)");
+            let bar: Box<dyn Base + Send + Sync> = Box::new(Bar);
+            assert!(format!("{:?}", bar).ends_with("::Bar"));
+        }
+    }
+    
+    mod wrapper {
+        use std::any::{Any, TypeId};
+        use super::super::wrapper::{self, Wrapper};
+        use super::super::Downcast;
+        
+        trait Base: Downcast {}
+        impl_downcast!(Base);
+        
+        #[derive(Debug, PartialEq)]
+        struct Foo(u32);
+        impl Base for Foo {}
+        
+        struct Instrumented<T>(T);
+        impl<T: Base> Base for Instrumented<T> {}
+        impl<T: Any> Wrapper for Instrumented<T> {
+            fn inner_any(&self) -> &dyn Any { &self.0 }
+            fn inner_any_mut(&mut self) -> &mut dyn Any { &mut self.0 }
+        }
+        
+        struct Cached<T> {
+            value: T,
+            hits: u32,
+        }
+        impl<T: Base> Base for Cached<T> {}
+        impl<T: Any> Wrapper for Cached<T> {
+            fn inner_any(&self) -> &dyn Any { &self.value }
+            fn inner_any_mut(&mut self) -> &mut dyn Any { &mut self.value }
+        }
+        
+        #[test]
+        fn test() {
+            wrapper::register::<Instrumented<Cached<Foo>>>();
+            wrapper::register::<Cached<Foo>>();
+            assert!(wrapper::is_wrapper(TypeId::of::<Cached<Foo>>()));
+            assert!(!wrapper::is_wrapper(TypeId::of::<Foo>()));
+            
+            let mut base: Box<dyn Base> = Box::new(Instrumented(Cached { value: Foo(1), hits: 0 }));
+            assert!(base.downcast_ref::<Foo>().is_none());
+            assert_eq!(base.downcast_ref_inner::<Foo>(), Some((&Foo(1), 2)));
+            let (cached, depth) = base.downcast_ref_inner::<Cached<Foo>>().unwrap();
+            assert_eq!((cached.hits, depth), (0, 1));
+            assert!(base.downcast_ref_inner::<Instrumented<Cached<Foo>>>().is_some_and(|(_, depth)| depth == 0));
+            assert!(base.downcast_ref_inner::<u32>().is_none());
+            
+            let (value, depth) = base.downcast_mut_inner::<Foo>().unwrap();
+            value.0 = 2;
+            assert_eq!(depth, 2);
+            assert_eq!(base.downcast_ref_inner::<Foo>(), Some((&Foo(2), 2)));
+            assert!(base.downcast_mut_inner::<u32>().is_none());
+            
+            // Unregistered wrappers are not looked through.
+            let base: Box<dyn Base> = Box::new(Instrumented(Foo(3)));
+            assert!(base.downcast_ref_inner::<Foo>().is_none());
+        }
+    }
+    
+    mod error {
+        use std::error::Error;
+        use std::fmt;
+        use std::any::Any;
+        use std::num::ParseIntError;
+        use std::sync::Arc;
+        use super::super::error::ErrorChain;
+        use super::super::wrapper::{self, Wrapper};
+        use super::super::Downcast;
+        
+        trait AppError: Downcast + Error {}
+        impl_downcast!(error AppError);
+        
+        // A chain alternating between 'AppError' implementors and plain errors:
+        // Startup -> Context -> Config -> Context -> ParseIntError.
+        #[derive(Debug)]
+        struct Startup {
+            attempts: u32,
+            source: Context,
+        }
+        impl fmt::Display for Startup {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "startup failed after {} attempts", self.attempts)
+            }
+        }
+        impl Error for Startup {
+            fn source(&self) -> Option<&(dyn Error + 'static)> { Some(&self.source) }
+        }
+        impl AppError for Startup {}
+        
+        #[derive(Debug)]
+        struct Config(Context);
+        impl fmt::Display for Config {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("invalid config") }
+        }
+        impl Error for Config {
+            fn source(&self) -> Option<&(dyn Error + 'static)> { Some(&self.0) }
+        }
+        impl AppError for Config {}
+        
+        #[derive(Debug)]
+        struct Context(&'static str, Box<dyn Error + Send + Sync>);
+        impl fmt::Display for Context {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str(self.0) }
+        }
+        impl Error for Context {
+            fn source(&self) -> Option<&(dyn Error + 'static)> { Some(&*self.1) }
+        }
+        
+        fn chain() -> Box<dyn AppError> {
+            let parse = "x".parse::<u32>().unwrap_err();
+            let config = Config(Context("reading port", Box::new(parse)));
+            Box::new(Startup { attempts: 1, source: Context("loading config", Box::new(config)) })
+        }
+        
+        #[test]
+        fn test_macro_head() {
+            let mut error = chain();
+            assert_eq!(error.find_cause::<Startup>().map(|startup| startup.attempts), Some(1));
+            assert_eq!(error.find_cause::<Context>().map(|context| context.0), Some("loading config"));
+            assert!(error.find_cause::<Config>().is_some());
+            assert!(error.find_cause::<fmt::Error>().is_none());
+            
+            let contexts: Vec<_> = error.causes_of::<Context>().map(|context| context.0).collect();
+            assert_eq!(contexts, ["loading config", "reading port"]);
+            assert_eq!(error.causes_of::<Startup>().count(), 1);
+            
+            assert!(error.root_cause_as::<ParseIntError>().is_some());
+            assert!(error.root_cause_as::<Context>().is_none());
+            
+            error.head_as_mut::<Startup>().unwrap().attempts = 2;
+            assert_eq!(error.to_string(), "startup failed after 2 attempts");
+            assert!(error.head_as_mut::<Config>().is_none());
+        }
+        
+        #[test]
+        fn test_plain_head() {
+            let error: Box<dyn Error + Send + Sync> = Box::new(Context("outer", Box::new(Config(Context(
+                "inner",
+                Box::new(fmt::Error),
+            )))));
+            assert_eq!(error.find_cause::<Context>().map(|context| context.0), Some("outer"));
+            assert!(error.find_cause::<Config>().is_some());
+            assert_eq!(error.causes_of::<Context>().count(), 2);
+            assert!(error.root_cause_as::<fmt::Error>().is_some());
+            
+            // Sized errors start the chain too, and a lone error is its own root.
+            assert!(fmt::Error.root_cause_as::<fmt::Error>().is_some());
+            let mut config = Config(Context("inner", Box::new(fmt::Error)));
+            assert!(config.find_cause::<fmt::Error>().is_some());
+            assert!(config.head_as_mut::<Config>().is_some());
+        }
+        
+        #[test]
+        fn test_pointer_head() {
+            // 'Box' and 'Arc' skip the error they hold in 'source', so the chain starts inside them.
+            let parse = || "x".parse::<u32>().unwrap_err();
+            assert!(Box::new(parse()).find_cause::<ParseIntError>().is_some());
+            assert!(Box::new(parse()).root_cause_as::<ParseIntError>().is_some());
+            let mut boxed = Box::new(Config(Context("inner", Box::new(parse()))));
+            assert_eq!(boxed.causes_of::<Config>().count(), 1);
+            assert!(boxed.root_cause_as::<ParseIntError>().is_some());
+            boxed.head_as_mut::<Config>().unwrap().0 .0 = "outer";
+            assert_eq!(boxed.find_cause::<Context>().map(|context| context.0), Some("outer"));
+            
+            let mut shared = Arc::new(parse());
+            assert!(shared.find_cause::<ParseIntError>().is_some());
+            assert!(shared.root_cause_as::<ParseIntError>().is_some());
+            assert!(shared.head_as_mut::<ParseIntError>().is_some());
+            let other = Arc::clone(&shared);
+            assert!(shared.head_as_mut::<ParseIntError>().is_none());
+            assert!(other.find_cause::<ParseIntError>().is_some());
+        }
+        
+        // A transparent wrapper, passing on the source of the error it wraps.
+        #[derive(Debug)]
+        struct Logged<E>(E);
+        impl<E: Error> fmt::Display for Logged<E> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { fmt::Display::fmt(&self.0, f) }
+        }
+        impl<E: Error> Error for Logged<E> {
+            fn source(&self) -> Option<&(dyn Error + 'static)> { self.0.source() }
+        }
+        impl<E: Error + 'static> AppError for Logged<E> {}
+        impl<E: Any> Wrapper for Logged<E> {
+            fn inner_any(&self) -> &dyn Any { &self.0 }
+            fn inner_any_mut(&mut self) -> &mut dyn Any { &mut self.0 }
+        }
+        
+        #[test]
+        fn test_wrapped_head() {
+            // Heads are matched by their own type, even when they are registered wrappers.
+            wrapper::register::<Logged<Config>>();
+            let config = || Config(Context("inner", Box::new(fmt::Error)));
+            let app: Box<dyn AppError> = Box::new(Logged(config()));
+            let plain: Box<dyn Error> = Box::new(Logged(config()));
+            assert!(app.find_cause::<Config>().is_none());
+            assert!(plain.find_cause::<Config>().is_none());
+            assert!(app.find_cause::<Logged<Config>>().is_some());
+            assert!(plain.find_cause::<Logged<Config>>().is_some());
+        }
+    }
+}
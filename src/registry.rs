@@ -0,0 +1,58 @@
+//! The global registries behind 'cast', 'debug' and 'wrapper'. Each entry is written by a single
+//! insertion, so a lock poisoned by a panicking reader or writer still holds consistent entries and
+//! is used as is.
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::sync::{PoisonError, RwLock};
+
+pub(crate) struct Registry<K, V> {
+    map: RwLock<BTreeMap<K, V>>,
+}
+
+impl<K, V> Registry<K, V> {
+    pub(crate) const fn new() -> Self {
+        Registry { map: RwLock::new(BTreeMap::new()) }
+    }
+}
+
+impl<K: Ord, V> Registry<K, V> {
+    pub(crate) fn insert(&self, key: K, value: V) {
+        self.map.write().unwrap_or_else(PoisonError::into_inner).insert(key, value);
+    }
+
+    /// Looks up the entry for 'key' and extracts a value from it while the lock is held.
+    pub(crate) fn find<R, F: FnOnce(&V) -> Option<R>>(&self, key: &K, extract: F) -> Option<R> {
+        self.map.read().unwrap_or_else(PoisonError::into_inner).get(key).and_then(extract)
+    }
+}
+
+impl<K: Ord, V: Copy> Registry<K, V> {
+    pub(crate) fn get(&self, key: &K) -> Option<V> {
+        self.find(key, |value| Some(*value))
+    }
+}
+
+// Entries are looked up by the 'TypeId' of the value they are then called with, so these downcasts
+// cannot fail.
+
+pub(crate) fn expect_ref<T: Any>(any: &dyn Any) -> &T {
+    match any.downcast_ref::<T>() {
+        Some(value) => value,
+        None => unreachable!("entry registered for another type"),
+    }
+}
+
+pub(crate) fn expect_mut<T: Any>(any: &mut dyn Any) -> &mut T {
+    match any.downcast_mut::<T>() {
+        Some(value) => value,
+        None => unreachable!("entry registered for another type"),
+    }
+}
+
+pub(crate) fn expect_box<T: Any>(any: Box<dyn Any>) -> Box<T> {
+    match any.downcast::<T>() {
+        Ok(value) => value,
+        Err(_) => unreachable!("entry registered for another type"),
+    }
+}
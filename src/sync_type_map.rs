@@ -0,0 +1,117 @@
+//! A thread-safe counterpart to 'TypeMap' for values shared as 'Arc<B>', where 'B' is the trait
+//! object of a trait declared through 'impl_downcast!(sync ...)'. The map is split into shards,
+//! each behind its own 'RwLock', so lookups of different types rarely contend and lookups of the
+//! same type only take a read lock.
+
+use std::any::{Any, TypeId};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::DowncastSync;
+use super::type_map::Erase;
+
+const SHARDS: usize = 16;
+
+type Shard<B> = RwLock<HashMap<TypeId, Arc<B>>>;
+
+/// Holds at most one value of each concrete type as an 'Arc<B>', readable from many threads.
+pub struct SyncTypeMap<B: ?Sized> {
+    shards: [Shard<B>; SHARDS],
+}
+
+impl<B: ?Sized + DowncastSync> SyncTypeMap<B> {
+    pub fn new() -> Self {
+        SyncTypeMap { shards: Default::default() }
+    }
+
+    fn shard(&self, id: TypeId) -> &Shard<B> {
+        let mut hasher = DefaultHasher::new();
+        id.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % SHARDS]
+    }
+
+    fn read(&self, id: TypeId) -> RwLockReadGuard<'_, HashMap<TypeId, Arc<B>>> {
+        self.shard(id).read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self, id: TypeId) -> RwLockWriteGuard<'_, HashMap<TypeId, Arc<B>>> {
+        self.shard(id).write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Counts the values by locking each shard in turn, so concurrent updates may be missed.
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.read().unwrap_or_else(PoisonError::into_inner).len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.read(TypeId::of::<T>()).contains_key(&TypeId::of::<T>())
+    }
+
+    /// Stores 'value', returning the value of the same type it replaces.
+    pub fn insert<T: Any + Send + Sync>(&self, value: T) -> Option<Arc<T>> where B: Erase<T> {
+        let erased = Arc::from(B::erase(value));
+        self.write(TypeId::of::<T>())
+            .insert(TypeId::of::<T>(), erased)
+            .map(restore::<B, T>)
+    }
+
+    pub fn get<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        let erased = self.read(TypeId::of::<T>()).get(&TypeId::of::<T>()).cloned();
+        erased.map(restore::<B, T>)
+    }
+
+    /// Returns the value of type 'T' as the trait object, without downcasting it.
+    pub fn get_erased<T: 'static>(&self) -> Option<Arc<B>> {
+        self.read(TypeId::of::<T>()).get(&TypeId::of::<T>()).cloned()
+    }
+
+    pub fn remove<T: Any + Send + Sync>(&self) -> Option<Arc<T>> {
+        let erased = self.write(TypeId::of::<T>()).remove(&TypeId::of::<T>());
+        erased.map(restore::<B, T>)
+    }
+
+    /// Returns the value of type 'T', inserting the result of 'default' if there is none.
+    ///
+    /// 'default' runs without holding any lock, so it may itself use the map, for instance to look
+    /// up the services a new service depends on. Threads racing to insert the same type may each
+    /// run 'default', but all of them get the single value that was stored.
+    pub fn get_or_insert_with<T, F>(&self, default: F) -> Arc<T>
+    where
+        T: Any + Send + Sync,
+        B: Erase<T>,
+        F: FnOnce() -> T,
+    {
+        if let Some(value) = self.get::<T>() {
+            return value;
+        }
+        let erased = Arc::from(B::erase(default()));
+        let erased = self
+            .write(TypeId::of::<T>())
+            .entry(TypeId::of::<T>())
+            .or_insert(erased)
+            .clone();
+        restore::<B, T>(erased)
+    }
+}
+
+impl<B: ?Sized + DowncastSync> Default for SyncTypeMap<B> {
+    fn default() -> Self {
+        SyncTypeMap::new()
+    }
+}
+
+fn restore<B: ?Sized + DowncastSync, T: Any + Send + Sync>(erased: Arc<B>) -> Arc<T> {
+    match DowncastSync::into_any_arc(erased).downcast::<T>() {
+        Ok(value) => value,
+        Err(_) => panic!("SyncTypeMap value stored under the TypeId of another type"),
+    }
+}
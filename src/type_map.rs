@@ -0,0 +1,278 @@
+//! A map holding at most one value per concrete type, stored as boxed trait objects 'B'. Works with
+//! 'dyn Any' (and its 'Send'/'Sync' variants) and with every trait declared through
+//! 'impl_downcast!'.
+
+use alloc::boxed::Box;
+use alloc::collections::btree_map::{self, BTreeMap};
+use core::any::{Any, TypeId};
+use core::marker::PhantomData;
+use core::mem;
+
+const MISMATCH: &str = "TypeMap value stored under the TypeId of another type";
+
+/// Implemented by trait objects that can hold values of type 'T': 'dyn Any' holds any ''static'
+/// type, and 'impl_downcast!' implements it for every implementor of the trait.
+pub trait Erase<T> {
+    fn erase(value: T) -> Box<Self>;
+    fn restore_ref(erased: &Self) -> Option<&T>;
+    fn restore_mut(erased: &mut Self) -> Option<&mut T>;
+    fn restore(erased: Box<Self>) -> Result<Box<T>, Box<Self>>;
+}
+
+macro_rules! impl_erase_any {
+    ($($object:ty: [$($bounds:tt)+]),+) => {$(
+        impl<T: $($bounds)+> Erase<T> for $object {
+            fn erase(value: T) -> Box<Self> {
+                Box::new(value)
+            }
+            fn restore_ref(erased: &Self) -> Option<&T> {
+                erased.downcast_ref::<T>()
+            }
+            fn restore_mut(erased: &mut Self) -> Option<&mut T> {
+                erased.downcast_mut::<T>()
+            }
+            fn restore(erased: Box<Self>) -> Result<Box<T>, Box<Self>> {
+                erased.downcast::<T>()
+            }
+        }
+    )+};
+}
+
+impl_erase_any!(
+    dyn Any: [Any],
+    dyn Any + Send: [Any + Send],
+    dyn Any + Send + Sync: [Any + Send + Sync]
+);
+
+fn restore<B: ?Sized + Erase<T>, T>(erased: Box<B>) -> T {
+    match B::restore(erased) {
+        Ok(value) => *value,
+        Err(_) => panic!("{}", MISMATCH),
+    }
+}
+
+/// Holds at most one value of each concrete type as a 'Box<B>'.
+pub struct TypeMap<B: ?Sized> {
+    map: BTreeMap<TypeId, Box<B>>,
+}
+
+impl<B: ?Sized> TypeMap<B> {
+    pub fn new() -> Self {
+        TypeMap { map: BTreeMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear()
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Stores 'value', returning the value of the same type it replaces.
+    pub fn insert<T: 'static>(&mut self, value: T) -> Option<T> where B: Erase<T> {
+        self.map
+            .insert(TypeId::of::<T>(), B::erase(value))
+            .map(restore::<B, T>)
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> where B: Erase<T> {
+        self.map.get(&TypeId::of::<T>()).and_then(|erased| B::restore_ref(erased))
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> where B: Erase<T> {
+        self.map.get_mut(&TypeId::of::<T>()).and_then(|erased| B::restore_mut(erased))
+    }
+
+    pub fn remove<T: 'static>(&mut self) -> Option<T> where B: Erase<T> {
+        self.map
+            .remove(&TypeId::of::<T>())
+            .map(restore::<B, T>)
+    }
+
+    pub fn entry<T: 'static>(&mut self) -> Entry<'_, B, T> where B: Erase<T> {
+        match self.map.entry(TypeId::of::<T>()) {
+            btree_map::Entry::Occupied(entry) => Entry::Occupied(OccupiedEntry {
+                entry,
+                marker: PhantomData,
+            }),
+            btree_map::Entry::Vacant(entry) => Entry::Vacant(VacantEntry {
+                entry,
+                marker: PhantomData,
+            }),
+        }
+    }
+
+    /// Iterates over the erased values, ordered by 'TypeId'.
+    pub fn iter(&self) -> Iter<'_, B> {
+        Iter { inner: self.map.values() }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, B> {
+        IterMut { inner: self.map.values_mut() }
+    }
+}
+
+impl<B: ?Sized> Default for TypeMap<B> {
+    fn default() -> Self {
+        TypeMap::new()
+    }
+}
+
+impl<B: ?Sized> IntoIterator for TypeMap<B> {
+    type Item = Box<B>;
+    type IntoIter = IntoIter<B>;
+
+    fn into_iter(self) -> IntoIter<B> {
+        IntoIter { inner: self.map.into_values() }
+    }
+}
+
+impl<'a, B: ?Sized> IntoIterator for &'a TypeMap<B> {
+    type Item = &'a B;
+    type IntoIter = Iter<'a, B>;
+
+    fn into_iter(self) -> Iter<'a, B> {
+        self.iter()
+    }
+}
+
+impl<'a, B: ?Sized> IntoIterator for &'a mut TypeMap<B> {
+    type Item = &'a mut B;
+    type IntoIter = IterMut<'a, B>;
+
+    fn into_iter(self) -> IterMut<'a, B> {
+        self.iter_mut()
+    }
+}
+
+pub struct Iter<'a, B: ?Sized + 'a> {
+    inner: btree_map::Values<'a, TypeId, Box<B>>,
+}
+
+impl<'a, B: ?Sized> Iterator for Iter<'a, B> {
+    type Item = &'a B;
+
+    fn next(&mut self) -> Option<&'a B> {
+        self.inner.next().map(|erased| &**erased)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, B: ?Sized> ExactSizeIterator for Iter<'a, B> {}
+
+pub struct IterMut<'a, B: ?Sized + 'a> {
+    inner: btree_map::ValuesMut<'a, TypeId, Box<B>>,
+}
+
+impl<'a, B: ?Sized> Iterator for IterMut<'a, B> {
+    type Item = &'a mut B;
+
+    fn next(&mut self) -> Option<&'a mut B> {
+        self.inner.next().map(|erased| &mut **erased)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, B: ?Sized> ExactSizeIterator for IterMut<'a, B> {}
+
+pub struct IntoIter<B: ?Sized> {
+    inner: btree_map::IntoValues<TypeId, Box<B>>,
+}
+
+impl<B: ?Sized> Iterator for IntoIter<B> {
+    type Item = Box<B>;
+
+    fn next(&mut self) -> Option<Box<B>> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<B: ?Sized> ExactSizeIterator for IntoIter<B> {}
+
+/// A view into the slot for type 'T' of a 'TypeMap', returned by 'TypeMap::entry'.
+pub enum Entry<'a, B: ?Sized + 'a, T: 'a> {
+    Occupied(OccupiedEntry<'a, B, T>),
+    Vacant(VacantEntry<'a, B, T>),
+}
+
+impl<'a, B: ?Sized + Erase<T>, T: 'a> Entry<'a, B, T> {
+    pub fn or_insert(self, default: T) -> &'a mut T {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> T>(self, default: F) -> &'a mut T {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut T where T: Default {
+        self.or_insert_with(T::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut T)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(ref mut entry) = self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+pub struct OccupiedEntry<'a, B: ?Sized + 'a, T: 'a> {
+    entry: btree_map::OccupiedEntry<'a, TypeId, Box<B>>,
+    marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, B: ?Sized + Erase<T>, T: 'a> OccupiedEntry<'a, B, T> {
+    pub fn get(&self) -> &T {
+        B::restore_ref(self.entry.get()).expect(MISMATCH)
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        B::restore_mut(self.entry.get_mut()).expect(MISMATCH)
+    }
+
+    pub fn into_mut(self) -> &'a mut T {
+        B::restore_mut(self.entry.into_mut()).expect(MISMATCH)
+    }
+
+    /// Replaces the value, returning the old one.
+    pub fn insert(&mut self, value: T) -> T {
+        mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> T {
+        restore::<B, T>(self.entry.remove())
+    }
+}
+
+pub struct VacantEntry<'a, B: ?Sized + 'a, T: 'a> {
+    entry: btree_map::VacantEntry<'a, TypeId, Box<B>>,
+    marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, B: ?Sized + Erase<T>, T: 'a> VacantEntry<'a, B, T> {
+    pub fn insert(self, value: T) -> &'a mut T {
+        B::restore_mut(self.entry.insert(B::erase(value))).expect(MISMATCH)
+    }
+}
@@ -0,0 +1,73 @@
+//! Downcasting through wrapper types. An implementor wrapped in an adapter such as
+//! 'Instrumented<Foo>' has the adapter as its concrete type, so 'downcast_ref::<Foo>()' fails.
+//! Wrappers implementing 'Wrapper' and registered with 'register' are looked through by the
+//! 'downcast_ref_inner' and 'downcast_mut_inner' methods generated by 'impl_downcast!', which also
+//! report how many wrappers were peeled off to reach the match.
+
+use std::any::{Any, TypeId};
+
+use super::registry::{self, Registry};
+
+/// Implemented by types that wrap another value, exposing it for downcasting.
+pub trait Wrapper: Any {
+    fn inner_any(&self) -> &dyn Any;
+    fn inner_any_mut(&mut self) -> &mut dyn Any;
+}
+
+#[derive(Clone, Copy)]
+struct Peeler {
+    inner: fn(&dyn Any) -> &dyn Any,
+    inner_mut: fn(&mut dyn Any) -> &mut dyn Any,
+}
+
+static REGISTRY: Registry<TypeId, Peeler> = Registry::new();
+
+fn inner<W: Wrapper>(any: &dyn Any) -> &dyn Any {
+    registry::expect_ref::<W>(any).inner_any()
+}
+
+fn inner_mut<W: Wrapper>(any: &mut dyn Any) -> &mut dyn Any {
+    registry::expect_mut::<W>(any).inner_any_mut()
+}
+
+/// Registers 'W' as a wrapper to be looked through. Generic wrappers are registered once for each
+/// type they wrap, as in 'register::<Cached<Foo>>()'.
+pub fn register<W: Wrapper>() {
+    REGISTRY.insert(TypeId::of::<W>(), Peeler { inner: inner::<W>, inner_mut: inner_mut::<W> });
+}
+
+/// Returns whether objects of the concrete type 'concrete' are registered wrappers.
+pub fn is_wrapper(concrete: TypeId) -> bool {
+    peeler(concrete).is_some()
+}
+
+fn peeler(concrete: TypeId) -> Option<Peeler> {
+    REGISTRY.get(&concrete)
+}
+
+/// Finds the value of type 'T' in 'any' or in the chain of registered wrappers inside it, along
+/// with its depth: 0 for 'any' itself, 1 for the value inside it, and so on.
+pub fn downcast_ref_inner<T: Any>(any: &dyn Any) -> Option<(&T, usize)> {
+    let mut current = any;
+    let mut depth = 0;
+    loop {
+        if let Some(value) = current.downcast_ref::<T>() {
+            return Some((value, depth));
+        }
+        current = (peeler(current.type_id())?.inner)(current);
+        depth += 1;
+    }
+}
+
+/// Mutable counterpart to 'downcast_ref_inner'.
+pub fn downcast_mut_inner<T: Any>(any: &mut dyn Any) -> Option<(&mut T, usize)> {
+    let mut current = any;
+    let mut depth = 0;
+    loop {
+        if current.is::<T>() {
+            return current.downcast_mut::<T>().map(|value| (value, depth));
+        }
+        current = (peeler((*current).type_id())?.inner_mut)(current);
+        depth += 1;
+    }
+}
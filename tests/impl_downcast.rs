@@ -0,0 +1,459 @@
+// Exercises every 'impl_downcast!' form from a downstream crate, which neither imports the macro
+// by name nor re-exports 'Downcast' at its root.
+extern crate wzDowncast;
+
+use std::rc::Rc;
+use std::sync::Arc;
+
+macro_rules! test_mod {
+    (
+        $test_name:ident,
+        trait $base_trait:path,
+        {$($def:tt)*}
+        $(sync {$($sync_test:tt)*})*
+        $(extra {$($extra_test:tt)*})*
+    ) => {
+        test_mod! {
+            $test_name, trait $base_trait {}, type dyn $base_trait, {$($def)*}
+            $(sync {$($sync_test)*})* $(extra {$($extra_test)*})*
+        }
+    };
+
+    (
+        $test_name:ident,
+        trait $base_trait:path {$($base_impl:tt)*},
+        type $base_type:ty,
+        {$($def:tt)*}
+        $(sync {$($sync_test:tt)*})*
+        $(extra {$($extra_test:tt)*})*
+    ) => {
+        mod $test_name {
+            use super::*;
+
+            // A trait that can be downcast.
+            $($def)*
+
+            // Concrete types implementing Base.
+            #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            struct Foo(u32);
+            impl $base_trait for Foo {$($base_impl)*}
+            #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            struct Bar;
+            impl $base_trait for Bar {$($base_impl)*}
+
+            #[test]
+            fn test() {
+                let mut base: Box<$base_type> = Box::new(Foo(42));
+                assert!(base.is::<Foo>());
+                assert!(!base.is::<Bar>());
+                assert_eq!(base.downcast_ref::<Foo>().map(|foo| foo.0), Some(42));
+                assert!(base.downcast_ref::<Bar>().is_none());
+
+                base.downcast_mut::<Foo>().unwrap().0 = 6*9;
+                assert!(base.downcast_mut::<Bar>().is_none());
+
+                // Metadata resolves through the trait object without importing 'Downcast'.
+                assert!(base.concrete_type_name().ends_with("::Foo"));
+                assert_eq!(base.concrete_type_id(), ::std::any::TypeId::of::<Foo>());
+                assert_eq!(base.concrete_size(), ::std::mem::size_of::<Foo>());
+                assert_eq!(base.concrete_align(), ::std::mem::align_of::<Foo>());
+                assert!(!base.concrete_needs_drop());
+
+                assert_eq!(wzDowncast::downcast_match!(base, _: Bar => 0, x: Foo => x.0, _ => 1), 6*9);
+                #[cfg(feature = "std")]
+                assert_eq!(base.downcast_ref_inner::<Foo>().map(|(foo, depth)| (foo.0, depth)), Some((6*9, 0)));
+
+                {
+                    use wzDowncast::iter::DowncastIter;
+                    let mut bases: Vec<Box<$base_type>> = vec![Box::new(Bar), Box::new(Foo(3))];
+                    bases.iter_mut().downcast_filter_mut::<Foo>().for_each(|foo| foo.0 += 1);
+                    assert_eq!(bases.iter().downcast_filter::<Foo>().map(|foo| foo.0).sum::<u32>(), 4);
+                    assert_eq!(bases.iter().map(|base| &**base).count_of::<Bar>(), 1);
+                    let (foos, rest) = bases.into_iter().partition_downcast::<Foo>();
+                    assert_eq!((foos.len(), rest.len()), (1, 1));
+                }
+
+                let mut map = wzDowncast::type_map::TypeMap::<$base_type>::new();
+                map.insert(Foo(1));
+                map.entry::<Foo>().or_insert(Foo(0)).0 += 1;
+                assert_eq!(map.get::<Foo>().map(|foo| foo.0), Some(2));
+                assert!(map.remove::<Bar>().is_none());
+
+                let base = base.downcast::<Bar>().err().unwrap();
+                assert_eq!(base.downcast::<Foo>().ok().unwrap().0, 6*9);
+
+                let base: Rc<$base_type> = Rc::new(Foo(7));
+                let base = base.downcast_rc::<Bar>().err().unwrap();
+                assert_eq!(base.downcast_rc::<Foo>().ok().unwrap().0, 7);
+
+                $({$($sync_test)*})*
+
+                $({$($extra_test)*})*
+            }
+        }
+    };
+}
+
+macro_rules! sync_test {
+    ($base_type:ty) => {{
+        let base: Arc<$base_type> = Arc::new(Foo(7));
+        let base = base.downcast_arc::<Bar>().err().unwrap();
+        assert_eq!(base.downcast_arc::<Foo>().ok().unwrap().0, 7);
+    }};
+}
+
+test_mod!(non_generic, trait Base, {
+    trait Base: wzDowncast::Downcast {}
+    wzDowncast::impl_downcast!(Base);
+});
+
+test_mod!(empty_generics, trait Base, {
+    trait Base: wzDowncast::Downcast {}
+    wzDowncast::impl_downcast!(Base<>);
+});
+
+test_mod!(generic, trait Base<u32, String>, {
+    trait Base<T, U>: wzDowncast::Downcast {}
+    wzDowncast::impl_downcast!(Base<T, U>);
+});
+
+test_mod!(constrained_generic, trait Base<u32>, {
+    trait Base<T: Copy>: wzDowncast::Downcast {}
+    wzDowncast::impl_downcast!(Base<T> where T: Copy);
+});
+
+test_mod!(concrete_parametrized, trait Base<u32>, {
+    trait Base<T>: wzDowncast::Downcast {}
+    wzDowncast::impl_downcast!(concrete Base<u32>);
+});
+
+test_mod!(sync_non_generic, trait Base, {
+    trait Base: wzDowncast::DowncastSync {}
+    wzDowncast::impl_downcast!(sync Base);
+} sync {
+    sync_test!(dyn Base)
+});
+
+test_mod!(sync_empty_generics, trait Base, {
+    trait Base: wzDowncast::DowncastSync {}
+    wzDowncast::impl_downcast!(sync Base<>);
+} sync {
+    sync_test!(dyn Base)
+});
+
+test_mod!(sync_generic, trait Base<u32, String>, {
+    trait Base<T, U>: wzDowncast::DowncastSync {}
+    wzDowncast::impl_downcast!(sync Base<T, U>);
+} sync {
+    sync_test!(dyn Base<u32, String>)
+});
+
+test_mod!(sync_constrained_generic, trait Base<u32>, {
+    trait Base<T: Copy>: wzDowncast::DowncastSync {}
+    wzDowncast::impl_downcast!(sync Base<T> where T: Copy);
+} sync {
+    sync_test!(dyn Base<u32>)
+});
+
+test_mod!(sync_concrete_parametrized, trait Base<u32>, {
+    trait Base<T>: wzDowncast::DowncastSync {}
+    wzDowncast::impl_downcast!(sync concrete Base<u32>);
+} sync {
+    sync_test!(dyn Base<u32>)
+});
+
+test_mod!(associated, trait Base {type H = f32;}, type dyn Base<H = f32>, {
+    trait Base: wzDowncast::Downcast { type H; }
+    wzDowncast::impl_downcast!(Base<H = O>);
+});
+
+test_mod!(generic_associated, trait Base<u32> {type H = f32;}, type dyn Base<u32, H = f32>, {
+    trait Base<T: Copy>: wzDowncast::Downcast { type H; }
+    wzDowncast::impl_downcast!(Base<T, H = O> where T: Copy);
+});
+
+test_mod!(concrete_associated, trait Base<u32> {type H = Vec<u8>;}, type dyn Base<u32, H = Vec<u8>>, {
+    trait Base<T>: wzDowncast::Downcast { type H; }
+    wzDowncast::impl_downcast!(concrete Base<u32, H = Vec<u8>>);
+});
+
+test_mod!(sync_associated, trait Base {type H = f32;}, type dyn Base<H = f32>, {
+    trait Base: wzDowncast::DowncastSync { type H; }
+    wzDowncast::impl_downcast!(sync Base<H = O>);
+} sync {
+    sync_test!(dyn Base<H = f32>)
+});
+
+test_mod!(sync_concrete_associated, trait Base {type H = f32;}, type dyn Base<H = f32>, {
+    trait Base: wzDowncast::DowncastSync { type H; }
+    wzDowncast::impl_downcast!(sync concrete Base<H = f32>);
+} sync {
+    sync_test!(dyn Base<H = f32>)
+});
+
+test_mod!(send, trait Base {}, type dyn Base + Send, {
+    trait Base: wzDowncast::Downcast {}
+    wzDowncast::impl_downcast!(Base);
+});
+
+test_mod!(sync_send_sync, trait Base<u32> {}, type dyn Base<u32> + Send + Sync, {
+    trait Base<T>: wzDowncast::DowncastSync {}
+    wzDowncast::impl_downcast!(sync Base<T>);
+} sync {
+    sync_test!(dyn Base<u32> + Send + Sync)
+});
+
+test_mod!(absolute_path, trait plugins::Base<u32> {}, type dyn plugins::Base<u32>, {
+    mod plugins {
+        pub trait Base<T: Copy>: ::wzDowncast::Downcast {}
+    }
+    wzDowncast::impl_downcast!(::absolute_path::plugins::Base<T> where T: Copy);
+});
+
+test_mod!(sync_concrete_path, trait plugins::Base<u32> {}, type dyn plugins::Base<u32>, {
+    mod plugins {
+        pub trait Base<T>: ::wzDowncast::DowncastSync {}
+    }
+    mod registry {
+        wzDowncast::impl_downcast!(sync concrete super::plugins::Base<u32>);
+    }
+} sync {
+    sync_test!(dyn plugins::Base<u32>)
+});
+
+test_mod!(clone_sync_generic, trait Base<u32>, {
+    trait Base<T: Copy>: wzDowncast::DowncastClone + wzDowncast::DowncastSync {}
+    wzDowncast::impl_downcast!(clone sync Base<T> where T: Copy);
+} sync {
+    sync_test!(dyn Base<u32>)
+} extra {
+    let base: Box<dyn Base<u32> + Send> = Box::new(Foo(5));
+    assert_eq!(base.clone().downcast_ref::<Foo>().map(|foo| foo.0), Some(5));
+});
+
+test_mod!(clone_concrete_path, trait plugins::Base<u32> {}, type dyn plugins::Base<u32>, {
+    mod plugins {
+        pub trait Base<T>: ::wzDowncast::DowncastClone {}
+    }
+    wzDowncast::impl_downcast!(clone concrete crate::clone_concrete_path::plugins::Base<u32>);
+} extra {
+    let base: Box<dyn plugins::Base<u32>> = Box::new(Bar);
+    assert!(base.clone_box().clone().is::<Bar>());
+});
+
+test_mod!(hash_generic, trait Base<u32>, {
+    trait Base<T>: wzDowncast::cmp::DynHash {}
+    wzDowncast::impl_downcast!(hash Base<T>);
+} extra {
+    let base: Box<dyn Base<u32>> = Box::new(Foo(5));
+    assert!(base == Box::new(Foo(5)) as Box<dyn Base<u32>>);
+    assert!(base != Box::new(Bar) as Box<dyn Base<u32>>);
+    let set: std::collections::HashSet<Box<dyn Base<u32>>> = vec![base, Box::new(Foo(5))].into_iter().collect();
+    assert_eq!(set.len(), 1);
+});
+
+test_mod!(ord_generic, trait Base<u32>, {
+    trait Base<T>: wzDowncast::cmp::DynOrd + wzDowncast::cmp::DynHash {}
+    fn key<T>(_: &dyn Base<T>) -> u8 { 0 }
+    wzDowncast::impl_downcast!(hash ord(key) Base<T>);
+} extra {
+    let set: std::collections::BTreeSet<Box<dyn Base<u32>>> =
+        vec![Box::new(Foo(2)) as Box<dyn Base<u32>>, Box::new(Bar), Box::new(Foo(1))].into_iter().collect();
+    let first = set.iter().next().unwrap();
+    assert_eq!(first.downcast_ref::<Bar>().is_some(), "Bar" < "Foo");
+    assert!(Box::new(Foo(1)) as Box<dyn Base<u32>> < Box::new(Foo(2)) as Box<dyn Base<u32>>);
+});
+
+test_mod!(debug_eq_generic, trait Base<u32>, {
+    trait Base<T>: wzDowncast::cmp::DynEq {}
+    wzDowncast::impl_downcast!(debug eq Base<T>);
+} extra {
+    let base: Box<dyn Base<u32>> = Box::new(Foo(5));
+    assert_eq!(&*base, &Foo(5) as &dyn Base<u32>);
+    assert!(format!("{:?}", Box::new(Bar) as Box<dyn Base<u32>>).ends_with("::Bar"));
+    // Formatters can only be registered with 'std'.
+    #[cfg(feature = "std")]
+    {
+        wzDowncast::debug::register::<Foo>();
+        assert_eq!(format!("{:?}", base), "Foo(5)");
+    }
+});
+
+#[cfg(feature = "std")]
+test_mod!(error_sync_generic, trait Base<u32>, {
+    trait Base<T>: wzDowncast::DowncastSync + std::error::Error {}
+    wzDowncast::impl_downcast!(error sync Base<T>);
+    impl std::fmt::Display for Foo {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { write!(f, "foo {}", self.0) }
+    }
+    impl std::error::Error for Foo {}
+    impl std::fmt::Display for Bar {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { f.write_str("bar") }
+    }
+    impl std::error::Error for Bar {}
+} sync {
+    sync_test!(dyn Base<u32>)
+} extra {
+    use wzDowncast::error::ErrorChain;
+    let mut base: Box<dyn Base<u32> + Send + Sync> = Box::new(Foo(5));
+    base.head_as_mut::<Foo>().unwrap().0 = 6;
+    assert_eq!(base.root_cause_as::<Foo>().map(|foo| foo.0), Some(6));
+    assert_eq!(base.causes_of::<Bar>().count(), 0);
+});
+
+test_mod!(keyword_module, trait sync::Base<u32> {}, type dyn sync::Base<u32>, {
+    mod sync {
+        pub trait Base<T: Copy>: ::wzDowncast::Downcast {}
+    }
+    wzDowncast::impl_downcast!(sync::Base<T> where T: Copy);
+});
+
+test_mod!(sync_keyword_module, trait sync::Base {}, type dyn sync::Base, {
+    mod sync {
+        pub trait Base: ::wzDowncast::DowncastSync {}
+    }
+    wzDowncast::impl_downcast!(sync sync::Base);
+} sync {
+    sync_test!(dyn sync::Base)
+});
+
+test_mod!(concrete_keyword_module, trait concrete::Base<u32> {}, type dyn concrete::Base<u32>, {
+    mod concrete {
+        pub trait Base<T>: ::wzDowncast::Downcast {}
+    }
+    wzDowncast::impl_downcast!(concrete concrete::Base<u32>);
+});
+
+test_mod!(concrete_keyword_path, trait concrete::Base {}, type dyn concrete::Base, {
+    mod concrete {
+        pub trait Base: ::wzDowncast::Downcast {}
+    }
+    wzDowncast::impl_downcast!(concrete::Base);
+});
+
+test_mod!(clone_keyword_module, trait clone::Base {}, type dyn clone::Base, {
+    mod clone {
+        pub trait Base: ::wzDowncast::Downcast {}
+    }
+    wzDowncast::impl_downcast!(clone::Base);
+});
+
+test_mod!(eq_keyword_module, trait eq::Base {}, type dyn eq::Base, {
+    mod eq {
+        pub trait Base: ::wzDowncast::Downcast {}
+    }
+    wzDowncast::impl_downcast!(eq::Base);
+});
+
+test_mod!(hash_keyword_module, trait hash::Base {}, type dyn hash::Base, {
+    mod hash {
+        pub trait Base: ::wzDowncast::Downcast {}
+    }
+    wzDowncast::impl_downcast!(hash::Base);
+});
+
+test_mod!(ord_keyword_module, trait ord::Base {}, type dyn ord::Base, {
+    mod ord {
+        pub trait Base: ::wzDowncast::Downcast {}
+    }
+    wzDowncast::impl_downcast!(ord::Base);
+});
+
+test_mod!(debug_keyword_module, trait debug::Base {}, type dyn debug::Base, {
+    mod debug {
+        pub trait Base: ::wzDowncast::Downcast {}
+    }
+    wzDowncast::impl_downcast!(debug::Base);
+});
+
+test_mod!(error_keyword_module, trait error::Base {}, type dyn error::Base, {
+    mod error {
+        pub trait Base: ::wzDowncast::Downcast {}
+    }
+    wzDowncast::impl_downcast!(error::Base);
+});
+
+test_mod!(error_option_module, trait error::AppError {}, type dyn error::AppError, {
+    mod error {
+        pub trait AppError: ::wzDowncast::Downcast + ::std::error::Error {}
+    }
+    wzDowncast::impl_downcast!(error error::AppError);
+    impl std::fmt::Display for Foo {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { write!(f, "foo {}", self.0) }
+    }
+    impl std::error::Error for Foo {}
+    impl std::fmt::Display for Bar {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { f.write_str("bar") }
+    }
+    impl std::error::Error for Bar {}
+});
+
+test_mod!(sync_absolute_path, trait plugins::Base {}, type dyn plugins::Base, {
+    mod plugins {
+        pub trait Base: ::wzDowncast::DowncastSync {}
+    }
+    wzDowncast::impl_downcast!(sync; ::sync_absolute_path::plugins::Base);
+} sync {
+    sync_test!(dyn plugins::Base)
+});
+
+test_mod!(concrete_absolute_path, trait plugins::Base<u32> {}, type dyn plugins::Base<u32>, {
+    mod plugins {
+        pub trait Base<T>: ::wzDowncast::Downcast {}
+    }
+    wzDowncast::impl_downcast!(concrete; ::concrete_absolute_path::plugins::Base<u32>);
+});
+
+test_mod!(sync_concrete_absolute_path, trait plugins::Base<u32> {}, type dyn plugins::Base<u32>, {
+    mod plugins {
+        pub trait Base<T>: ::wzDowncast::DowncastSync {}
+    }
+    wzDowncast::impl_downcast!(sync concrete; ::sync_concrete_absolute_path::plugins::Base<u32>);
+} sync {
+    sync_test!(dyn plugins::Base<u32>)
+});
+
+test_mod!(options_absolute_path, trait plugins::Base<u32> {}, type dyn plugins::Base<u32>, {
+    mod plugins {
+        pub trait Base<T>: ::wzDowncast::DowncastClone + ::wzDowncast::cmp::DynOrd + ::wzDowncast::cmp::DynHash {}
+    }
+    fn key<T>(_: &dyn plugins::Base<T>) -> u8 { 0 }
+    wzDowncast::impl_downcast!(clone eq hash ord(key) debug; ::options_absolute_path::plugins::Base<T>);
+} extra {
+    let base: Box<dyn plugins::Base<u32>> = Box::new(Foo(5));
+    assert!(*base.clone() == *base);
+    assert!(base < Box::new(Foo(6)) as Box<dyn plugins::Base<u32>>);
+    assert!(format!("{:?}", base).ends_with("::Foo"));
+});
+
+#[cfg(feature = "std")]
+test_mod!(error_absolute_path, trait plugins::AppError {}, type dyn plugins::AppError, {
+    mod plugins {
+        pub trait AppError: ::wzDowncast::Downcast + ::std::error::Error {}
+    }
+    wzDowncast::impl_downcast!(error; ::error_absolute_path::plugins::AppError);
+    impl std::fmt::Display for Foo {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { write!(f, "foo {}", self.0) }
+    }
+    impl std::error::Error for Foo {}
+    impl std::fmt::Display for Bar {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { f.write_str("bar") }
+    }
+    impl std::error::Error for Bar {}
+} extra {
+    use wzDowncast::error::ErrorChain;
+    let base: Box<dyn plugins::AppError> = Box::new(Foo(5));
+    assert_eq!(base.find_cause::<Foo>().map(|foo| foo.0), Some(5));
+});
+
+test_mod!(mixed_generic, trait Base<'static, u32, 8>, {
+    trait Base<'a, T: Copy, const N: usize>: wzDowncast::Downcast {}
+    wzDowncast::impl_downcast!(Base<'a, T, const N: usize> where T: Copy);
+});
+
+test_mod!(sync_concrete_mixed, trait Base<'static, u32, 8>, {
+    trait Base<'a, T, const N: usize>: wzDowncast::DowncastSync {}
+    wzDowncast::impl_downcast!(sync concrete Base<'static, u32, 8>);
+} sync {
+    sync_test!(dyn Base<'static, u32, 8>)
+});